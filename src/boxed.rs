@@ -0,0 +1,218 @@
+//! Type-erased, heap-allocated effects.
+//!
+//! Everything in this module boxes its stages, trading the zero-cost
+//! composition of `BoundEffect` for the ability to assemble effects whose
+//! shape is only known at runtime.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Assembles an effect at runtime from a base effect and a list of steps,
+/// each of which transforms the previous value.
+///
+/// This is the allocating path: the base effect and every step are boxed, and
+/// running the built effect makes one dynamic call per step. Prefer `bind`
+/// when the chain is known at compile time.
+pub struct EffectBuilder<A> {
+    base: Box<dyn FnOnce() -> A>,
+    steps: Vec<Box<dyn FnOnce(A) -> A>>,
+}
+
+impl<A: 'static> EffectBuilder<A> {
+    /// Creates a builder whose chain starts by running `base`.
+    pub fn new<E>(base: E) -> Self
+        where E: FnOnce() -> A + 'static,
+    {
+        EffectBuilder {
+            base: Box::new(base),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Appends a step which is applied to the value produced by the chain so
+    /// far.
+    pub fn step<F>(mut self, f: F) -> Self
+        where F: FnOnce(A) -> A + 'static,
+    {
+        self.steps.push(Box::new(f));
+        self
+    }
+
+    /// Returns the number of steps added so far.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns `true` if no steps have been added.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Finishes the chain, producing an effect which runs the base effect and
+    /// then each step in the order they were added.
+    pub fn build(self) -> impl FnOnce() -> A {
+        let EffectBuilder { base, steps } = self;
+        move || steps.into_iter().fold(base(), |a, step| step(a))
+    }
+}
+
+type Stage<A> = Box<dyn FnOnce(A) -> A>;
+
+/// An effect followed by a pipeline of pure transformations of its result,
+/// created by `EffectMonad::cascade`.
+///
+/// Stages added with `then` are boxed, so the cascade keeps the same type
+/// however many there are. Running it runs the base effect and then each
+/// stage, in the order they were added.
+pub struct Cascade<E>
+    where E: FnOnce<()>,
+{
+    base: E,
+    stages: Vec<Stage<E::Output>>,
+}
+
+impl<E> Cascade<E>
+    where E: FnOnce<()>,
+{
+    pub(crate) fn new(base: E) -> Self {
+        Cascade {
+            base,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Appends a stage which transforms the value produced so far.
+    pub fn then<F>(mut self, f: F) -> Self
+        where F: FnOnce(E::Output) -> E::Output + 'static,
+    {
+        self.stages.push(Box::new(f));
+        self
+    }
+}
+
+impl<E> FnOnce<()> for Cascade<E>
+    where E: FnOnce<()>,
+{
+    type Output = E::Output;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = self.base.call_once(());
+        self.stages.into_iter().fold(a, |a, stage| stage(a))
+    }
+}
+
+/// Creates an effect which applies each of `stages` to `input`, in order,
+/// returning the final value.
+///
+/// This is the runtime counterpart to `Cascade`: the list of stages can be
+/// assembled from data rather than written out in a chain. Every stage is a
+/// separate allocation, and running the pipeline makes one dynamic call per
+/// stage.
+pub fn pipeline<A>(stages: Vec<Box<dyn FnOnce(A) -> A>>, input: A) -> impl FnOnce() -> A {
+    move || stages.into_iter().fold(input, |a, stage| stage(a))
+}
+
+/// A boxed effect, whose type doesn't depend on how it was built.
+///
+/// `BoundEffect` nests the types of every stage, so effects built
+/// recursively, like those of a parser, end up with types that can't be
+/// written down. Boxing erases them, at the cost of an allocation per
+/// `BoxedEffect` and a dynamic call to run it.
+pub struct BoxedEffect<A>(Box<dyn FnOnce() -> A>);
+
+impl<A> BoxedEffect<A> {
+    /// Boxes `e`.
+    pub fn new<E>(e: E) -> Self
+        where E: FnOnce() -> A + 'static,
+    {
+        BoxedEffect(Box::new(e))
+    }
+}
+
+impl<A> FnOnce<()> for BoxedEffect<A> {
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.0)()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn effect_builder_runs_steps_in_order() {
+        let mut builder = EffectBuilder::new(|| 1);
+        for step in [2, 3, 4] {
+            builder = builder.step(move |a| a * 10 + step);
+        }
+        assert_eq!(builder.len(), 3);
+        assert_eq!(builder.build()(), 1234);
+    }
+
+    #[test]
+    fn effect_builder_without_steps_runs_base() {
+        let builder = EffectBuilder::new(|| 5);
+        assert!(builder.is_empty());
+        assert_eq!(builder.build()(), 5);
+    }
+
+    #[test]
+    fn cascade_runs_stages_in_order() {
+        use std::rc::Rc;
+        use core::cell::RefCell;
+        use EffectMonad;
+
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let stage = |tag: u32, f: fn(i32) -> i32| {
+            let order = order.clone();
+            move |a| {
+                order.borrow_mut().push(tag);
+                f(a)
+            }
+        };
+        let cascade = (|| 2).cascade()
+            .then(stage(1, |a| a + 1))
+            .then(stage(2, |a| a * 10))
+            .then(stage(3, |a| a - 4));
+        assert_eq!(cascade(), 26);
+        assert_eq!(*order.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn pipeline_applies_stages_in_order() {
+        use std::rc::Rc;
+        use core::cell::RefCell;
+
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let stage = |tag: u32, f: fn(i32) -> i32| -> Box<dyn FnOnce(i32) -> i32> {
+            let order = order.clone();
+            Box::new(move |a| {
+                order.borrow_mut().push(tag);
+                f(a)
+            })
+        };
+        let stages = vec![stage(1, |a| a + 1), stage(2, |a| a * 10), stage(3, |a| a - 4)];
+        assert_eq!(pipeline(stages, 2)(), 26);
+        assert_eq!(*order.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn bind_boxed_builds_recursive_chains() {
+        use EffectMonad;
+
+        // Sums n + (n - 1) + ... + 1, one stage per term
+        fn sum_to(n: u32) -> BoxedEffect<u32> {
+            if n == 0 {
+                BoxedEffect::new(|| 0)
+            } else {
+                (move || n).bind_boxed(move |a| {
+                    let rest = sum_to(n - 1);
+                    BoxedEffect::new(move || a + rest())
+                })
+            }
+        }
+
+        let depth = "4".parse().unwrap();
+        assert_eq!(sum_to(depth)(), 10);
+    }
+}
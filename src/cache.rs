@@ -0,0 +1,85 @@
+//! Memoization of effect results.
+
+use core::cell::RefCell;
+use core::hash::Hash;
+use std::collections::HashMap;
+
+/// Memoizes parameterized effects by key.
+///
+/// The cache is shared by reference: `get_or_run` only needs `&self`, so one
+/// cache can serve many effects. It isn't `Sync`; use one per thread.
+pub struct KeyedCache<K, V> {
+    values: RefCell<HashMap<K, V>>,
+}
+
+impl<K, V> KeyedCache<K, V>
+    where K: Hash + Eq,
+          V: Clone,
+{
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        KeyedCache {
+            values: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached value for `key`, running `e` and caching its result
+    /// only if there isn't one yet.
+    ///
+    /// The cache isn't borrowed while `e` runs, so `e` may itself use the
+    /// cache.
+    pub fn get_or_run<E>(&self, key: K, e: E) -> V
+        where E: FnOnce() -> V,
+    {
+        if let Some(v) = self.values.borrow().get(&key) {
+            return v.clone();
+        }
+        let v = e();
+        self.values.borrow_mut().insert(key, v.clone());
+        v
+    }
+
+    /// Returns the number of cached values.
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    /// Returns `true` if nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.values.borrow().is_empty()
+    }
+}
+
+impl<K, V> Default for KeyedCache<K, V>
+    where K: Hash + Eq,
+          V: Clone,
+{
+    fn default() -> Self {
+        KeyedCache::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn keyed_cache_runs_once_per_key() {
+        let runs = Cell::new(0);
+        let cache = KeyedCache::new();
+        let square = |n: i32| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                n * n
+            }
+        };
+        assert_eq!(cache.get_or_run(3, square(3)), 9);
+        assert_eq!(cache.get_or_run(3, square(3)), 9);
+        assert_eq!(runs.get(), 1);
+        assert_eq!(cache.get_or_run(4, square(4)), 16);
+        assert_eq!(runs.get(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,84 @@
+//! Go-style deferred cleanup effects.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// A stack of cleanup effects, run in reverse order of registration.
+///
+/// The deferred effects run when the stack is dropped, including while
+/// unwinding from a panic, or earlier with an explicit call to `run`.
+#[derive(Default)]
+pub struct DeferStack<'a> {
+    effects: Vec<Box<dyn FnOnce() + 'a>>,
+}
+
+impl<'a> DeferStack<'a> {
+    /// Creates an empty stack.
+    pub fn new() -> Self {
+        DeferStack::default()
+    }
+
+    /// Registers `f` to be run before everything registered so far.
+    pub fn push<F>(&mut self, f: F)
+        where F: FnOnce() + 'a,
+    {
+        self.effects.push(Box::new(f));
+    }
+
+    /// Returns the number of effects waiting to run.
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    /// Returns `true` if no effects are waiting to run.
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Runs every deferred effect now, most recently registered first.
+    pub fn run(mut self) {
+        self.run_all();
+    }
+
+    fn run_all(&mut self) {
+        while let Some(f) = self.effects.pop() {
+            f();
+        }
+    }
+}
+
+impl<'a> Drop for DeferStack<'a> {
+    fn drop(&mut self) {
+        self.run_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::RefCell;
+
+    #[test]
+    fn defer_stack_runs_in_reverse() {
+        let order = RefCell::new(Vec::new());
+        let mut stack = DeferStack::new();
+        for i in 1..4 {
+            let order = &order;
+            stack.push(move || order.borrow_mut().push(i));
+        }
+        assert_eq!(stack.len(), 3);
+        stack.run();
+        assert_eq!(order.into_inner(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn defer_stack_runs_on_drop() {
+        let order = RefCell::new(Vec::new());
+        {
+            let mut stack = DeferStack::new();
+            stack.push(|| order.borrow_mut().push("first"));
+            stack.push(|| order.borrow_mut().push("second"));
+        }
+        assert_eq!(order.into_inner(), vec!["second", "first"]);
+    }
+}
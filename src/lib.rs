@@ -1,15 +1,98 @@
 //! This module contains Purescript-inspired effects monads for rust
 //!
 //! Here, an effect is defined an evaluatable function.
+//!
+//! The crate is `no_std`. Combinators which allocate are only available with
+//! the `alloc` feature, and those which need the rest of the standard library,
+//! like threads, clocks or the environment, with the `std` feature, which
+//! implies `alloc`.
 #![no_std]
-#![feature(fn_traits, unboxed_closures)]
+#![feature(fn_traits, unboxed_closures, tuple_trait)]
+#![cfg_attr(feature = "unstable", feature(specialization))]
+#![cfg_attr(feature = "unstable", allow(incomplete_features))]
 
-#[cfg(test)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate std;
 
+#[cfg(feature = "alloc")]
+pub mod boxed;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "alloc")]
+pub mod defer;
+pub mod monoid;
+pub mod option;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod panic;
+pub mod result;
+#[cfg(feature = "std")]
+pub mod retry;
+#[cfg(feature = "rng")]
+pub mod rng;
+pub mod seq;
+#[cfg(feature = "unstable")]
+pub mod shape;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod thread;
+#[cfg(feature = "std")]
+pub mod time;
+
+pub use option::OptionEffect;
+#[cfg(feature = "std")]
+pub use panic::PanicEffect;
+pub use result::ResultEffect;
+#[cfg(feature = "std")]
+pub use retry::RetryEffect;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Lifts an expression or block into an effect by wrapping it in a `move`
+/// closure.
+///
+/// The `ref` form, `effect_map!(ref expr)`, is for effects which return a
+/// `&'a mut` reference into data they captured by reference. The closure is
+/// pinned to `FnOnce() -> &'a mut T`, so the returned reference lives for the
+/// captured borrow's lifetime `'a` and can be handed to later stages.
+///
+/// The `try` form, `effect_map!(try expr)`, produces a closure returning
+/// `Ok(expr)`, so `?` can be used inside `expr`. As with any `?`, the error
+/// type is converted with `From`, so the closure's error type has to be fixed
+/// by the surrounding code, e.g. by annotating the type of the result.
+///
+/// The `as` form, `effect_map!(as Eff: expr)`, wraps the closure in the `Eff`
+/// newtype (or any alias of it, named by any path, e.g.
+/// `effect_monad::Eff`), giving a value which can be stored in struct fields
+/// and returned from helpers as `Eff<impl FnOnce() -> T>`.
+///
+/// The `mut` form, `effect_map!(mut expr)`, produces a closure without
+/// `move`, so variables are captured by reference (mutable reference where
+/// `expr` mutates them) instead of being moved in. The result is an `FnMut`
+/// which can be run repeatedly, each run observing the previous runs' side
+/// effects.
 #[macro_export]
 macro_rules! effect_map {
+    ( ref $e:expr ) => {
+        $crate::ref_effect(move || $e)
+    };
+    ( mut $e:expr ) => {
+        || $e
+    };
+    ( as :: $( $t:ident )::+ : $e:expr ) => {
+        ::$( $t )::+::from(move || $e)
+    };
+    ( as $( $t:ident )::+ : $e:expr ) => {
+        $( $t )::+::from(move || $e)
+    };
+    ( try $e:expr ) => {
+        move || Ok($e)
+    };
     ( $e:expr ) => {
         move || $e
     };
@@ -18,6 +101,139 @@ macro_rules! effect_map {
     };
 }
 
+/// Constrains `f` to an `FnOnce` returning a reference with the captured
+/// lifetime `'a`. Used by the `ref` form of `effect_map!`.
+#[doc(hidden)]
+#[inline(always)]
+pub fn ref_effect<'a, T: ?Sized + 'a, F: FnOnce() -> &'a mut T>(f: F) -> F {
+    f
+}
+
+/// Binds a continuation to an effect without spelling out the `move`s.
+///
+/// `bind_closure!(effect, a => body)` expands to
+/// `effect.bind(move |a| move || body)`, so the value produced by `effect` is
+/// moved into the continuation and `body` becomes the next effect.
+#[macro_export]
+macro_rules! bind_closure {
+    ( $e:expr, $a:pat => $body:expr ) => {
+        $crate::EffectMonad::bind($e, move |$a| move || $body)
+    };
+}
+
+/// Builds a `bind` chain which prints every intermediate value to stderr.
+///
+/// `debug_chain![effect => f => g]` is `effect.bind(f).bind(g)`, except that
+/// the value produced by each stage is printed with `eprintln!` alongside the
+/// stage's source text before being passed on. Every stage's output must
+/// implement `Debug`. This is a development aid; it requires the `std`
+/// feature.
+///
+/// The `with` form, `debug_chain![with log; effect => f => g]`, passes each
+/// stage's source text and value to `log` instead, which is called as
+/// `log(label, &value as &dyn Debug)`. `log` is copied into every stage, so
+/// it must be `Copy`, e.g. a reference to a closure or a function. This form
+/// doesn't need `std`.
+#[macro_export]
+macro_rules! debug_chain {
+    ( @acc $log:expr ; $acc:expr ; ) => {
+        $acc
+    };
+    ( @acc $log:expr ; $acc:expr ; => $f:expr $( => $rest:expr )* ) => {
+        $crate::debug_chain!(@acc $log ; $crate::EffectMonad::bind($acc, move |a| {
+            $crate::debug_effect_with(stringify!($f), ($f)(a), $log)
+        }) ; $( => $rest )*)
+    };
+    ( with $log:expr ; $e:expr $( => $f:expr )* ) => {{
+        let log = $log;
+        $crate::debug_chain!(@acc log ; $crate::debug_effect_with(stringify!($e), $e, log) ; $( => $f )*)
+    }};
+    ( $e:expr $( => $f:expr )* ) => {
+        $crate::debug_chain!(with $crate::debug_to_stderr ; $e $( => $f )*)
+    };
+}
+
+/// Wraps `e` so that its result is passed to `log`, labelled with `label`.
+/// Used by `debug_chain!`.
+#[doc(hidden)]
+pub fn debug_effect_with<A, E, L>(label: &'static str, e: E, log: L) -> impl FnOnce() -> A
+    where A: core::fmt::Debug,
+          E: FnOnce() -> A,
+          L: FnOnce(&'static str, &dyn core::fmt::Debug),
+{
+    move || {
+        let a = e();
+        log(label, &a);
+        a
+    }
+}
+
+/// Prints `value` to stderr, labelled with `label`. The default log for
+/// `debug_chain!`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn debug_to_stderr(label: &'static str, value: &dyn core::fmt::Debug) {
+    eprintln!("{}: {:?}", label, value);
+}
+
+/// Overrides a thread-local for the duration of an effect.
+///
+/// `with_local!(KEY = value => effect)` is an effect which sets the
+/// thread-local `KEY`, a `RefCell`, to `value`, runs `effect`, and then puts
+/// the previous value back, even if `effect` panics. Only the current
+/// thread's copy of `KEY` is touched. This requires the `std` feature.
+#[macro_export]
+macro_rules! with_local {
+    ( $key:path = $value:expr => $e:expr ) => {
+        $crate::with_local_effect(&$key, $value, $e)
+    };
+}
+
+/// Wraps `e` so that `key` holds `value` while it runs. Used by
+/// `with_local!`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn with_local_effect<T, A, E>(key: &'static std::thread::LocalKey<core::cell::RefCell<T>>, value: T, e: E) -> impl FnOnce() -> A
+    where E: FnOnce() -> A,
+{
+    move || {
+        let _restore = LocalRestore {
+            key,
+            previous: Some(key.replace(value)),
+        };
+        e()
+    }
+}
+
+/// Puts a thread-local's previous value back when dropped, so that
+/// `with_local!` restores it even while unwinding.
+#[cfg(feature = "std")]
+struct LocalRestore<T: 'static> {
+    key: &'static std::thread::LocalKey<core::cell::RefCell<T>>,
+    previous: Option<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T: 'static> Drop for LocalRestore<T> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            self.key.set(previous);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static CORRELATION_ID: core::cell::RefCell<Option<u64>> = const { core::cell::RefCell::new(None) };
+}
+
+/// Returns the correlation id of the `EffectMonad::with_correlation_id`
+/// effect running on this thread, if there is one.
+#[cfg(feature = "std")]
+pub fn correlation_id() -> Option<u64> {
+    CORRELATION_ID.with_borrow(|id| *id)
+}
+
 /// Helper enum for acting as a resolve function.
 ///
 /// Ideally, we would use a closure instead of this type, but this type exists
@@ -26,7 +242,7 @@ pub enum ResolveFn<T> {
     Const(T),
 }
 
-impl<T, Args> FnOnce<Args> for ResolveFn<T> {
+impl<T, Args: core::marker::Tuple> FnOnce<Args> for ResolveFn<T> {
     type Output = T;
 
     #[inline(always)]
@@ -59,7 +275,7 @@ pub trait EffectMonad<A>: Sized {
     /// returning the value of the second Monad.
     ///
     /// Shorthand for
-    /// ```rust
+    /// ```rust,ignore
     /// effectMonad.bind(|_| someOtherEffectMonad);
     /// ```
     #[inline(always)]
@@ -68,6 +284,462 @@ pub trait EffectMonad<A>: Sized {
     {
         self.bind(eb.into())
     }
+
+    /// Validates the effect's result with `pred`, producing `Ok` with the
+    /// original value if the predicate passes and `Err(err)` otherwise.
+    #[inline(always)]
+    fn ensure<E, F>(self, pred: F, err: E) -> EnsureEffect<Self, F, E>
+        where F: FnOnce(&A) -> bool,
+    {
+        EnsureEffect {
+            e: self,
+            pred,
+            err,
+        }
+    }
+
+    /// Runs the effect for its side effects, then returns `value` in place of
+    /// the effect's own result.
+    #[inline(always)]
+    fn discard_with<B>(self, value: B) -> DiscardWithEffect<Self, B> {
+        DiscardWithEffect {
+            e: self,
+            value,
+        }
+    }
+
+    /// Turns the effect into an iterator which yields its result exactly
+    /// once. The effect isn't run until the first call to `next`.
+    #[inline(always)]
+    fn once_iter(self) -> EffectOnceIter<Self> {
+        EffectOnceIter {
+            e: Some(self),
+        }
+    }
+
+    /// Runs the effect, passes a clone of its result to `sink`, and returns
+    /// the original result.
+    #[inline(always)]
+    fn tee<S>(self, sink: S) -> TeeEffect<Self, S>
+        where A: Clone,
+              S: FnOnce(A),
+    {
+        TeeEffect {
+            e: self,
+            sink,
+        }
+    }
+
+    /// Like `tee`, but feeds a clone of the result to each of `sinks`, in
+    /// order. The result is cloned `sinks.len()` times, and the original is
+    /// returned.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn broadcast<S>(self, sinks: alloc::vec::Vec<S>) -> BroadcastEffect<Self, S>
+        where Self: FnOnce() -> A,
+              A: Clone,
+              S: FnOnce(A),
+    {
+        BroadcastEffect {
+            e: self,
+            sinks,
+        }
+    }
+
+    /// Like `tee`, but sends the clone down `sender`. A disconnected
+    /// receiver is ignored.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn tee_sender(self, sender: std::sync::mpsc::Sender<A>) -> TeeEffect<Self, thread::SenderSink<A>>
+        where A: Clone,
+    {
+        self.tee(thread::SenderSink(sender))
+    }
+
+    /// Runs the effect, then runs the effect produced by `f` for each element
+    /// of its iterable result, in order, collecting their results.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn flat_map_iter<B, F, E2, Item>(self, f: F) -> FlatMapIterEffect<Self, F>
+        where A: IntoIterator<Item = Item>,
+              F: FnMut(Item) -> E2,
+              E2: FnOnce() -> B,
+    {
+        FlatMapIterEffect {
+            e: self,
+            f,
+        }
+    }
+
+    /// Runs the effect and then `eb`, combining their results with
+    /// `Semigroup::combine`. The left operand is this effect's result.
+    #[inline(always)]
+    fn combine<Eb>(self, eb: Eb) -> CombineEffect<Self, Eb>
+        where Eb: FnOnce() -> A,
+              A: monoid::Semigroup,
+    {
+        CombineEffect {
+            ea: self,
+            eb,
+        }
+    }
+
+    /// Runs the effect, then runs `after`, returning the effect's result
+    /// unchanged. Useful for cleanup or notification steps.
+    #[inline(always)]
+    fn then_run<F>(self, after: F) -> ThenRunEffect<Self, F>
+        where F: FnOnce(),
+    {
+        ThenRunEffect {
+            e: self,
+            after,
+        }
+    }
+
+    /// Runs `before`, then runs the effect, returning the effect's result.
+    /// Useful for setup hooks.
+    #[inline(always)]
+    fn prepend<F>(self, before: F) -> PrependEffect<Self, F>
+        where F: FnOnce(),
+    {
+        PrependEffect {
+            e: self,
+            before,
+        }
+    }
+
+    /// Wraps the effect so that it runs at most once, however many times the
+    /// wrapper is called. The first call returns `Some` with the result and
+    /// every later call returns `None` without running anything.
+    #[inline(always)]
+    fn fuse(self) -> Fuse<Self> {
+        Fuse {
+            e: Some(self),
+        }
+    }
+
+    /// Runs the effect and applies `f` to its result, returning both the
+    /// original value and the transformed one, so later stages can use
+    /// either.
+    ///
+    /// `f` consumes its input, so it is given a clone of the result; this is
+    /// why `A` must be `Clone`.
+    #[inline(always)]
+    fn keep_input<B, F>(self, f: F) -> KeepInputEffect<Self, F>
+        where Self: FnOnce() -> A,
+              A: Clone,
+              F: FnOnce(A) -> B,
+    {
+        KeepInputEffect {
+            e: self,
+            f,
+        }
+    }
+
+    /// Wraps the effect so that it counts how many times it has been run.
+    /// The count is read with `Counted::count`, which makes this handy for
+    /// asserting how often combinators like `retry_policy` call an effect.
+    #[inline(always)]
+    fn count_runs(self) -> Counted<Self>
+        where Self: Fn() -> A,
+    {
+        Counted {
+            e: self,
+            runs: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs the effect, incrementing the counter `name` in `metrics` and
+    /// recording how long the effect took, in microseconds, as a sample of
+    /// `name`.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn metered<M>(self, metrics: M, name: &'static str) -> metrics::MeteredEffect<Self, M>
+        where M: metrics::Metrics,
+    {
+        metrics::MeteredEffect {
+            e: self,
+            metrics,
+            name,
+        }
+    }
+
+    /// Names the effect as a profiling stage. Each time it runs, the time it
+    /// takes is added to the stage's total in a process-global registry,
+    /// which is read with `time::stage_timings`.
+    ///
+    /// Stages nest: wrapping a chain which contains stages times the whole
+    /// chain as well as each inner stage.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn stage(self, name: &'static str) -> time::Stage<Self> {
+        time::Stage {
+            e: self,
+            name,
+        }
+    }
+
+    /// Runs the effect, then issues a memory fence with the given `ordering`
+    /// before returning its result.
+    ///
+    /// With `Release` (or stronger), every write the effect made, including
+    /// relaxed atomic stores, happens-before any write the caller makes after
+    /// the fence. A thread which reads one of those later writes and then
+    /// issues an `Acquire` fence is guaranteed to see the effect's writes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ordering` is `Ordering::Relaxed`, which isn't a valid fence
+    /// ordering. This is checked here rather than when the effect is run, so
+    /// the effect never runs only to have its result lost.
+    #[inline(always)]
+    fn with_fence(self, ordering: Ordering) -> WithFenceEffect<Self> {
+        assert!(ordering != Ordering::Relaxed, "a fence can't have Relaxed ordering");
+        WithFenceEffect {
+            e: self,
+            ordering,
+        }
+    }
+
+    /// Runs `gate` and, only if it returns `true`, runs the effect, returning
+    /// `Some` with its result. If the gate is closed, the effect never runs
+    /// and `None` is returned.
+    ///
+    /// The gate is evaluated when the returned effect is run, not when it is
+    /// built, so it sees the state of a feature flag at that moment.
+    #[inline(always)]
+    fn gated<G>(self, gate: G) -> GatedEffect<Self, G>
+        where Self: FnOnce() -> A,
+              G: FnOnce() -> bool,
+    {
+        GatedEffect {
+            e: self,
+            gate,
+        }
+    }
+
+    /// Runs the effect and, only if `pred` accepts its result, runs the
+    /// effect produced by `f`, returning `Some` with its result. If `pred`
+    /// rejects the result, `f` is never called and `None` is returned.
+    #[inline(always)]
+    fn bind_if<B, Eb, F, P>(self, pred: P, f: F) -> BindIfEffect<Self, P, F>
+        where P: FnOnce(&A) -> bool,
+              F: FnOnce(A) -> Eb,
+              Eb: FnOnce() -> B,
+    {
+        BindIfEffect {
+            e: self,
+            pred,
+            f,
+        }
+    }
+
+    /// Starts running the effect on a new thread, returning a handle which
+    /// can be joined to get its result.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn spawn(self) -> thread::EffectHandle<A>
+        where Self: FnOnce() -> A + Send + 'static,
+              A: Send + 'static,
+    {
+        self.spawn_with(&thread::SpawnConfig::new())
+    }
+
+    /// Like `spawn`, but spawns the effect's thread as configured by
+    /// `config`.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn spawn_with(self, config: &thread::SpawnConfig) -> thread::EffectHandle<A>
+        where Self: FnOnce() -> A + Send + 'static,
+              A: Send + 'static,
+    {
+        thread::EffectHandle(config.spawn(self))
+    }
+
+    /// Starts running the effect on a new thread when run, giving up if it
+    /// doesn't finish by `deadline`. This is `thread::timeout` with an
+    /// absolute deadline, which is handy when several effects share one.
+    ///
+    /// If the deadline has already passed when the returned effect is run,
+    /// the effect isn't started at all. Otherwise, as with `timeout`, an
+    /// effect which misses the deadline keeps running in the background.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn until_deadline(self, deadline: std::time::Instant) -> thread::UntilDeadlineEffect<Self>
+        where Self: FnOnce() -> A + Send + 'static,
+              A: Send + 'static,
+    {
+        thread::UntilDeadlineEffect {
+            e: self,
+            deadline,
+        }
+    }
+
+    /// Runs the effect with `id` as the current correlation id, so that every
+    /// stage and hook of the chain, e.g. those added with `hooked` or
+    /// `trace_into`, can read it with `correlation_id`. The previous id, if
+    /// any, is restored afterwards, even if the effect panics.
+    ///
+    /// The id is thread-local, so effects which the chain starts on other
+    /// threads don't see it.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn with_correlation_id(self, id: u64) -> WithCorrelationIdEffect<Self>
+        where Self: FnOnce() -> A,
+    {
+        WithCorrelationIdEffect {
+            e: self,
+            id,
+        }
+    }
+
+    /// Wraps the effect with instrumentation hooks: `pre` runs first, then
+    /// the effect, then `post` with a reference to the result, which is then
+    /// returned.
+    #[inline(always)]
+    fn hooked<Pre, Post>(self, pre: Pre, post: Post) -> HookedEffect<Self, Pre, Post>
+        where Pre: FnOnce(),
+              Post: FnOnce(&A),
+    {
+        HookedEffect {
+            e: self,
+            pre,
+            post,
+        }
+    }
+
+    /// Marks a side-effecting effect which returns `()` as a `UnitEffect`,
+    /// for APIs which should only accept effects run for their side effects.
+    ///
+    /// Only unit effects can be wrapped:
+    ///
+    /// ```compile_fail
+    /// use effect_monad::EffectMonad;
+    ///
+    /// let _ = (|| 5).as_unit();
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    #[inline(always)]
+    fn as_unit(self) -> UnitEffect<Self>
+        where Self: FnOnce(),
+    {
+        UnitEffect(self)
+    }
+
+    /// Wraps the result of the effect in `Ok`, so that an infallible effect
+    /// can be used where a fallible one is expected.
+    ///
+    /// The error type is `core::convert::Infallible`, which has no values,
+    /// so the result is statically known to always be `Ok`.
+    #[inline(always)]
+    fn infallible(self) -> InfallibleEffect<Self> {
+        InfallibleEffect {
+            e: self,
+        }
+    }
+
+    /// Runs the effect, logging a clone of its result with `log`, and returns
+    /// the original result. This is `tee` under a name which reads better
+    /// when tracing the stages of a long chain.
+    #[inline(always)]
+    fn trace_into<L>(self, log: L) -> TeeEffect<Self, L>
+        where A: Clone,
+              L: FnOnce(A),
+    {
+        self.tee(log)
+    }
+
+    /// Like `bind`, but boxes the composed effect so that its type doesn't
+    /// grow with each stage. This allows chains to be built recursively, at
+    /// the cost of an allocation per stage.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn bind_boxed<B, F>(self, f: F) -> boxed::BoxedEffect<B>
+        where Self: FnOnce() -> A + 'static,
+              F: FnOnce(A) -> boxed::BoxedEffect<B> + 'static,
+    {
+        boxed::BoxedEffect::new(move || f(self())())
+    }
+
+    /// Runs the effect with the environment variable `key` set to `value`,
+    /// then restores its previous value, or unsets it if it had none. The
+    /// variable is restored even if the effect panics.
+    ///
+    /// The environment is process-global: while the effect runs, every other
+    /// thread sees the variable too, and scoping variables from several
+    /// threads at once may not restore the original environment. This is
+    /// meant for tests which run serially.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn with_env<K, V>(self, key: K, value: V) -> WithEnvEffect<Self, K, V>
+        where Self: FnOnce() -> A,
+              K: AsRef<std::ffi::OsStr>,
+              V: AsRef<std::ffi::OsStr>,
+    {
+        WithEnvEffect {
+            e: self,
+            key,
+            value,
+        }
+    }
+
+    /// Runs the effect, then tries each of the fallible continuations in `fs`
+    /// in order, returning the first `Ok`. Continuations after it are never
+    /// run. If every one fails, their errors are returned in order.
+    ///
+    /// Each continuation consumes its input, so it is given a clone of the
+    /// effect's result; this is why `A` must be `Clone`.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn bind_any<B, E, F, Eb>(self, fs: alloc::vec::Vec<F>) -> BindAnyEffect<Self, F>
+        where Self: FnOnce() -> A,
+              A: Clone,
+              F: FnOnce(A) -> Eb,
+              Eb: FnOnce() -> Result<B, E>,
+    {
+        BindAnyEffect {
+            e: self,
+            fs,
+        }
+    }
+
+    /// Starts a `Cascade` of pure transformations to apply to the effect's
+    /// result, added one at a time with `Cascade::then`. Each stage is boxed.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn cascade(self) -> boxed::Cascade<Self>
+        where Self: FnOnce() -> A,
+    {
+        boxed::Cascade::new(self)
+    }
+
+    /// Runs the effect and, only if it returns `true`, runs `eb`, returning
+    /// its result. This is `&&` at the effect level: if the effect returns
+    /// `false`, `eb` is never run.
+    #[inline(always)]
+    fn and<Eb>(self, eb: Eb) -> AndEffect<Self, Eb>
+        where Self: FnOnce() -> bool,
+              Eb: FnOnce() -> bool,
+    {
+        AndEffect {
+            ea: self,
+            eb,
+        }
+    }
+
+    /// Runs the effect and, only if it returns `false`, runs `eb`, returning
+    /// its result. This is `||` at the effect level: if the effect returns
+    /// `true`, `eb` is never run.
+    #[inline(always)]
+    fn or<Eb>(self, eb: Eb) -> OrEffect<Self, Eb>
+        where Self: FnOnce() -> bool,
+              Eb: FnOnce() -> bool,
+    {
+        OrEffect {
+            ea: self,
+            eb,
+        }
+    }
 }
 
 impl<T, A> EffectMonad<A> for T
@@ -110,40 +782,611 @@ fn bind_effects<A, B, Ea, Eb, F>(first: Ea, f: F) -> BoundEffect<Ea, F>
 {
     BoundEffect {
         ea: first,
-        f: f,
+        f,
     }
 }
 
-#[cfg(test)]
-mod public_test {
-    use super::*;
+/// Effect returned by `EffectMonad::ensure`.
+pub struct EnsureEffect<Ea, F, E> {
+    e: Ea,
+    pred: F,
+    err: E,
+}
 
-    #[test]
-    fn effect_monad_bind_performs() {
-        let mut x: isize = 0;
-        let px = &mut x as *mut isize;
-        (|| unsafe {
-            *px += 2;
-        }).bind_ignore_contents(|| unsafe {
-            *px -= 1;
-        })();
-        assert_eq!(x, 1);
+impl<A, Ea, F, E> FnOnce<()> for EnsureEffect<Ea, F, E>
+    where Ea: FnOnce() -> A,
+          F: FnOnce(&A) -> bool,
+{
+    type Output = Result<A, E>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        if (self.pred)(&a) {
+            Ok(a)
+        } else {
+            Err(self.err)
+        }
     }
+}
 
-    #[test]
-    fn effect_monad_bind_performs_sequentially() {
-        let mut x: isize = 3;
-        let px = &mut x as *mut isize;
-        (|| unsafe {
-            *px *= 2;
-        }).bind_ignore_contents(|| unsafe {
-            *px -= 1;
-        })();
-        assert_eq!(x, 5);
+/// Effect returned by `EffectMonad::discard_with`.
+pub struct DiscardWithEffect<Ea, B> {
+    e: Ea,
+    value: B,
+}
+
+impl<A, Ea, B> FnOnce<()> for DiscardWithEffect<Ea, B>
+    where Ea: FnOnce() -> A,
+{
+    type Output = B;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)();
+        self.value
     }
+}
 
-    #[test]
-    fn effect_monad_bind_binds() {
+/// A nameable wrapper around an effect.
+///
+/// Closure types can't be written down, which makes effects awkward to store
+/// in struct fields. Wrapping one in `Eff` lets it be named through a generic
+/// parameter, or returned from a helper as `Eff<impl FnOnce() -> A>`.
+pub struct Eff<F>(pub F);
+
+impl<A, F> FnOnce<()> for Eff<F>
+    where F: FnOnce() -> A,
+{
+    type Output = A;
+    #[inline(always)]
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.0)()
+    }
+}
+
+impl<F> From<F> for Eff<F> {
+    fn from(f: F) -> Self {
+        Eff(f)
+    }
+}
+
+/// Iterator returned by `EffectMonad::once_iter`.
+pub struct EffectOnceIter<E> {
+    e: Option<E>,
+}
+
+impl<A, E> Iterator for EffectOnceIter<E>
+    where E: FnOnce() -> A,
+{
+    type Item = A;
+
+    fn next(&mut self) -> Option<A> {
+        self.e.take().map(|e| e())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = if self.e.is_some() { 1 } else { 0 };
+        (n, Some(n))
+    }
+}
+
+/// Effect returned by `EffectMonad::tee`.
+pub struct TeeEffect<Ea, S> {
+    e: Ea,
+    sink: S,
+}
+
+impl<A, Ea, S> FnOnce<()> for TeeEffect<Ea, S>
+    where Ea: FnOnce() -> A,
+          A: Clone,
+          S: FnOnce(A),
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        (self.sink)(a.clone());
+        a
+    }
+}
+
+/// Effect returned by `EffectMonad::broadcast`.
+#[cfg(feature = "alloc")]
+pub struct BroadcastEffect<Ea, S> {
+    e: Ea,
+    sinks: alloc::vec::Vec<S>,
+}
+
+#[cfg(feature = "alloc")]
+impl<A, Ea, S> FnOnce<()> for BroadcastEffect<Ea, S>
+    where Ea: FnOnce() -> A,
+          A: Clone,
+          S: FnOnce(A),
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        for sink in self.sinks {
+            sink(a.clone());
+        }
+        a
+    }
+}
+
+/// Effect returned by `EffectMonad::flat_map_iter`.
+#[cfg(feature = "alloc")]
+pub struct FlatMapIterEffect<Ea, F> {
+    e: Ea,
+    f: F,
+}
+
+#[cfg(feature = "alloc")]
+impl<A, B, Ea, E2, F, Item> FnOnce<()> for FlatMapIterEffect<Ea, F>
+    where Ea: FnOnce() -> A,
+          A: IntoIterator<Item = Item>,
+          F: FnMut(Item) -> E2,
+          E2: FnOnce() -> B,
+{
+    type Output = alloc::vec::Vec<B>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let mut f = self.f;
+        (self.e)().into_iter().map(|item| f(item)()).collect()
+    }
+}
+
+/// Effect returned by `EffectMonad::with_env`.
+#[cfg(feature = "std")]
+pub struct WithEnvEffect<Ea, K, V> {
+    e: Ea,
+    key: K,
+    value: V,
+}
+
+#[cfg(feature = "std")]
+impl<A, Ea, K, V> FnOnce<()> for WithEnvEffect<Ea, K, V>
+    where Ea: FnOnce() -> A,
+          K: AsRef<std::ffi::OsStr>,
+          V: AsRef<std::ffi::OsStr>,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let restore = EnvRestore {
+            previous: std::env::var_os(&self.key),
+            key: self.key,
+        };
+        std::env::set_var(&restore.key, self.value);
+        (self.e)()
+    }
+}
+
+/// Puts an environment variable back the way it was when dropped, so that
+/// `WithEnvEffect` restores it even while unwinding.
+#[cfg(feature = "std")]
+struct EnvRestore<K: AsRef<std::ffi::OsStr>> {
+    key: K,
+    previous: Option<std::ffi::OsString>,
+}
+
+#[cfg(feature = "std")]
+impl<K: AsRef<std::ffi::OsStr>> Drop for EnvRestore<K> {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => std::env::set_var(&self.key, previous),
+            None => std::env::remove_var(&self.key),
+        }
+    }
+}
+
+/// Effect returned by `EffectMonad::bind_any`.
+#[cfg(feature = "alloc")]
+pub struct BindAnyEffect<Ea, F> {
+    e: Ea,
+    fs: alloc::vec::Vec<F>,
+}
+
+#[cfg(feature = "alloc")]
+impl<A, B, E, Ea, Eb, F> FnOnce<()> for BindAnyEffect<Ea, F>
+    where Ea: FnOnce() -> A,
+          A: Clone,
+          F: FnOnce(A) -> Eb,
+          Eb: FnOnce() -> Result<B, E>,
+{
+    type Output = Result<B, alloc::vec::Vec<E>>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        let mut errors = alloc::vec::Vec::new();
+        for f in self.fs {
+            match f(a.clone())() {
+                Ok(b) => return Ok(b),
+                Err(err) => errors.push(err),
+            }
+        }
+        Err(errors)
+    }
+}
+
+/// Effect returned by `EffectMonad::with_correlation_id`.
+#[cfg(feature = "std")]
+pub struct WithCorrelationIdEffect<Ea> {
+    e: Ea,
+    id: u64,
+}
+
+#[cfg(feature = "std")]
+impl<A, Ea> FnOnce<()> for WithCorrelationIdEffect<Ea>
+    where Ea: FnOnce() -> A,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        with_local_effect(&CORRELATION_ID, Some(self.id), self.e)()
+    }
+}
+
+/// Effect returned by `EffectMonad::combine`.
+pub struct CombineEffect<Ea, Eb> {
+    ea: Ea,
+    eb: Eb,
+}
+
+impl<A, Ea, Eb> FnOnce<()> for CombineEffect<Ea, Eb>
+    where Ea: FnOnce() -> A,
+          Eb: FnOnce() -> A,
+          A: monoid::Semigroup,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.ea)();
+        a.combine((self.eb)())
+    }
+}
+
+/// Effect returned by `EffectMonad::then_run`.
+pub struct ThenRunEffect<Ea, F> {
+    e: Ea,
+    after: F,
+}
+
+impl<A, Ea, F> FnOnce<()> for ThenRunEffect<Ea, F>
+    where Ea: FnOnce() -> A,
+          F: FnOnce(),
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        (self.after)();
+        a
+    }
+}
+
+/// Effect returned by `EffectMonad::prepend`.
+pub struct PrependEffect<Ea, F> {
+    e: Ea,
+    before: F,
+}
+
+impl<A, Ea, F> FnOnce<()> for PrependEffect<Ea, F>
+    where Ea: FnOnce() -> A,
+          F: FnOnce(),
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.before)();
+        (self.e)()
+    }
+}
+
+/// Effect returned by `EffectMonad::fuse`, which can be called repeatedly but
+/// only runs the underlying effect the first time.
+pub struct Fuse<E> {
+    e: Option<E>,
+}
+
+impl<A, E> FnOnce<()> for Fuse<E>
+    where E: FnOnce() -> A,
+{
+    type Output = Option<A>;
+    extern "rust-call" fn call_once(mut self, _: ()) -> Self::Output {
+        self.call_mut(())
+    }
+}
+
+impl<A, E> FnMut<()> for Fuse<E>
+    where E: FnOnce() -> A,
+{
+    extern "rust-call" fn call_mut(&mut self, _: ()) -> Self::Output {
+        self.e.take().map(|e| e())
+    }
+}
+
+/// Effect returned by `EffectMonad::keep_input`.
+pub struct KeepInputEffect<Ea, F> {
+    e: Ea,
+    f: F,
+}
+
+impl<A, B, Ea, F> FnOnce<()> for KeepInputEffect<Ea, F>
+    where Ea: FnOnce() -> A,
+          A: Clone,
+          F: FnOnce(A) -> B,
+{
+    type Output = (A, B);
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        let b = (self.f)(a.clone());
+        (a, b)
+    }
+}
+
+/// Wrapper around a repeatable effect, returned by `EffectMonad::count_runs`,
+/// which counts how many times the effect has been run.
+pub struct Counted<E> {
+    e: E,
+    runs: AtomicUsize,
+}
+
+impl<E> Counted<E> {
+    /// Returns how many times the effect has been run so far.
+    #[inline(always)]
+    pub fn count(&self) -> usize {
+        self.runs.load(Ordering::SeqCst)
+    }
+}
+
+impl<A, E> FnOnce<()> for Counted<E>
+    where E: Fn() -> A,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        self.call(())
+    }
+}
+
+impl<A, E> FnMut<()> for Counted<E>
+    where E: Fn() -> A,
+{
+    extern "rust-call" fn call_mut(&mut self, _: ()) -> Self::Output {
+        self.call(())
+    }
+}
+
+impl<A, E> Fn<()> for Counted<E>
+    where E: Fn() -> A,
+{
+    extern "rust-call" fn call(&self, _: ()) -> Self::Output {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        (self.e)()
+    }
+}
+
+/// Effect returned by `EffectMonad::with_fence`.
+pub struct WithFenceEffect<Ea> {
+    e: Ea,
+    ordering: Ordering,
+}
+
+impl<A, Ea> FnOnce<()> for WithFenceEffect<Ea>
+    where Ea: FnOnce() -> A,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        core::sync::atomic::fence(self.ordering);
+        a
+    }
+}
+
+/// Effect returned by `EffectMonad::gated`.
+pub struct GatedEffect<Ea, G> {
+    e: Ea,
+    gate: G,
+}
+
+impl<A, Ea, G> FnOnce<()> for GatedEffect<Ea, G>
+    where Ea: FnOnce() -> A,
+          G: FnOnce() -> bool,
+{
+    type Output = Option<A>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        if (self.gate)() {
+            Some((self.e)())
+        } else {
+            None
+        }
+    }
+}
+
+/// Effect returned by `EffectMonad::bind_if`.
+pub struct BindIfEffect<Ea, P, F> {
+    e: Ea,
+    pred: P,
+    f: F,
+}
+
+impl<A, B, Ea, Eb, F, P> FnOnce<()> for BindIfEffect<Ea, P, F>
+    where Ea: FnOnce() -> A,
+          P: FnOnce(&A) -> bool,
+          F: FnOnce(A) -> Eb,
+          Eb: FnOnce() -> B,
+{
+    type Output = Option<B>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let a = (self.e)();
+        if (self.pred)(&a) {
+            Some((self.f)(a)())
+        } else {
+            None
+        }
+    }
+}
+
+/// Effect returned by `EffectMonad::hooked`.
+pub struct HookedEffect<Ea, Pre, Post> {
+    e: Ea,
+    pre: Pre,
+    post: Post,
+}
+
+impl<A, Ea, Pre, Post> FnOnce<()> for HookedEffect<Ea, Pre, Post>
+    where Ea: FnOnce() -> A,
+          Pre: FnOnce(),
+          Post: FnOnce(&A),
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.pre)();
+        let a = (self.e)();
+        (self.post)(&a);
+        a
+    }
+}
+
+/// An effect which returns `()`, created by `EffectMonad::as_unit`.
+///
+/// Taking a `UnitEffect` rather than a generic effect guarantees, in the
+/// type, that no result is being silently dropped.
+pub struct UnitEffect<E>(E);
+
+impl<E> FnOnce<()> for UnitEffect<E>
+    where E: FnOnce(),
+{
+    type Output = ();
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.0)()
+    }
+}
+
+/// Effect returned by `EffectMonad::infallible`.
+pub struct InfallibleEffect<Ea> {
+    e: Ea,
+}
+
+impl<A, Ea> FnOnce<()> for InfallibleEffect<Ea>
+    where Ea: FnOnce() -> A,
+{
+    type Output = Result<A, core::convert::Infallible>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        Ok((self.e)())
+    }
+}
+
+/// Effect returned by `EffectMonad::and`.
+pub struct AndEffect<Ea, Eb> {
+    ea: Ea,
+    eb: Eb,
+}
+
+impl<Ea, Eb> FnOnce<()> for AndEffect<Ea, Eb>
+    where Ea: FnOnce() -> bool,
+          Eb: FnOnce() -> bool,
+{
+    type Output = bool;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.ea)() && (self.eb)()
+    }
+}
+
+/// Effect returned by `EffectMonad::or`.
+pub struct OrEffect<Ea, Eb> {
+    ea: Ea,
+    eb: Eb,
+}
+
+impl<Ea, Eb> FnOnce<()> for OrEffect<Ea, Eb>
+    where Ea: FnOnce() -> bool,
+          Eb: FnOnce() -> bool,
+{
+    type Output = bool;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.ea)() || (self.eb)()
+    }
+}
+
+/// Coerces a non-capturing effect into a plain `fn` pointer.
+///
+/// Every stage built this way has the same type, `fn() -> A`, so chains made
+/// from them share a single monomorphized `BoundEffect` instead of one per
+/// closure. The trade-off is size: a non-capturing closure takes no space at
+/// all, while each `fn` pointer stage takes a pointer's worth (8 bytes on
+/// 64-bit targets), and calling it is an indirect call. Only closures which
+/// capture nothing can be coerced to a `fn` pointer; anything that needs
+/// captured state must stay a closure.
+#[inline(always)]
+pub fn from_fn_ptr<A>(f: fn() -> A) -> fn() -> A {
+    f
+}
+
+/// Partially applies the `fn` pointer `f` to `a`, producing an effect which
+/// calls `f(a)` when run.
+///
+/// This is handy for building tables of effects out of plain functions and
+/// fixed arguments.
+#[inline(always)]
+pub fn apply_fn<A, B>(f: fn(A) -> B, a: A) -> impl FnOnce() -> B {
+    move || f(a)
+}
+
+/// Composes the effect-producing function `f` with itself `n` times,
+/// producing a function which threads its argument through `n` runs of the
+/// effects `f` produces, in sequence.
+///
+/// This is the `n`-fold `bind` of `f`: `compose_n(2, f)(a)` behaves like
+/// `f(a).bind(f)`. With `n` zero, the resulting effect returns its argument
+/// unchanged.
+pub fn compose_n<A, F, E>(n: usize, f: F) -> impl FnOnce(A) -> ComposeNEffect<A, F>
+    where F: Fn(A) -> E,
+          E: FnOnce() -> A,
+{
+    move |a| ComposeNEffect {
+        a,
+        f,
+        n,
+    }
+}
+
+/// Effect produced by the function returned from `compose_n`.
+pub struct ComposeNEffect<A, F> {
+    a: A,
+    f: F,
+    n: usize,
+}
+
+impl<A, E, F> FnOnce<()> for ComposeNEffect<A, F>
+    where F: Fn(A) -> E,
+          E: FnOnce() -> A,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let f = self.f;
+        (0..self.n).fold(self.a, |a, _| f(a)())
+    }
+}
+
+#[cfg(test)]
+mod public_test {
+    use super::*;
+
+    #[test]
+    fn effect_monad_bind_performs() {
+        let mut x: isize = 0;
+        let px = &mut x as *mut isize;
+        (|| unsafe {
+            *px += 2;
+        }).bind_ignore_contents(|| unsafe {
+            *px -= 1;
+        })();
+        assert_eq!(x, 1);
+    }
+
+    #[test]
+    fn effect_monad_bind_performs_sequentially() {
+        let mut x: isize = 3;
+        let px = &mut x as *mut isize;
+        (|| unsafe {
+            *px *= 2;
+        }).bind_ignore_contents(|| unsafe {
+            *px -= 1;
+        })();
+        assert_eq!(x, 5);
+    }
+
+    #[test]
+    fn effect_monad_bind_binds() {
         let mut x: isize = 0;
         let px = &mut x as *mut isize;
         (|| unsafe {
@@ -158,6 +1401,20 @@ mod public_test {
     }
 
     #[test]
+    #[cfg(feature = "std")]
+    fn effect_monad_bind_runs_stages_in_order() {
+        let tracker = testing::SeqTracker::new();
+        let t = &tracker;
+        let chain = t.track(1, || 1)
+            .bind(move |a| t.track(2, move || a + 1))
+            .bind(move |a| t.track(3, move || a * 10));
+        assert!(tracker.is_empty());
+        assert_eq!(chain(), 20);
+        tracker.assert_order(&[1, 2, 3]);
+    }
+
+    #[test]
+    #[allow(clippy::precedence)]
     fn println_can_be_mapped_as_effect() {
         effect_map!(println!("hello")).bind_ignore_contents(effect_map!(println!("goodbye")))();
     }
@@ -173,6 +1430,7 @@ mod public_test {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure_call)]
     fn effect_can_implicitly_borrow() {
         let mut x = 1;
         {
@@ -207,6 +1465,616 @@ mod public_test {
         }
         assert_eq!(x, 7);
     }
+
+    #[test]
+    fn effect_map_ref_returns_borrow() {
+        let mut x: i32 = 1;
+        {
+            let px = &mut x;
+            effect_map!(ref &mut *px).bind(|r: &mut i32| effect_map!(*r += 41))();
+        }
+        assert_eq!(x, 42);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn debug_chain_logs_each_stage() {
+        use core::cell::RefCell;
+        use core::fmt::Debug;
+
+        let lines = RefCell::new(std::vec::Vec::new());
+        let log = |label: &'static str, value: &dyn Debug| {
+            lines.borrow_mut().push(format!("{}: {:?}", label, value));
+        };
+        let result = debug_chain![with &log; || 1 => |a: i32| move || a + 1 => |b: i32| move || b * 10]();
+
+        assert_eq!(result, 20);
+        let lines = lines.into_inner();
+        let values: std::vec::Vec<&str> = lines.iter()
+            .map(|line| line.rsplit(": ").next().unwrap())
+            .collect();
+        assert_eq!(values, ["1", "2", "20"]);
+        assert!(lines[0].starts_with("|| 1: "));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn debug_chain_prints_to_stderr() {
+        assert_eq!(debug_chain![|| 1 => |a: i32| move || a + 1](), 2);
+    }
+
+    #[test]
+    fn ensure_passes_value_through() {
+        assert_eq!((|| 4).ensure(|a| a % 2 == 0, "odd")(), Ok(4));
+    }
+
+    #[test]
+    fn ensure_fails_with_error() {
+        assert_eq!((|| 3).ensure(|a| a % 2 == 0, "odd")(), Err("odd"));
+    }
+
+    #[test]
+    fn effect_map_try_propagates_errors() {
+        use core::num::ParseIntError;
+
+        let parse = |s: &'static str| effect_map!(try s.parse::<i32>()? + 1);
+        let ok: Result<i32, ParseIntError> = parse("41")();
+        let err: Result<i32, ParseIntError> = parse("x")();
+        assert_eq!(ok, Ok(42));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    #[allow(clippy::precedence)]
+    fn discard_with_returns_constant() {
+        let mut x = 0;
+        {
+            let px = &mut x;
+            assert_eq!(effect_map!({
+                *px += 1;
+                *px
+            }).discard_with("done")(), "done");
+        }
+        assert_eq!(x, 1);
+    }
+
+    #[test]
+    fn effect_map_as_can_be_stored() {
+        struct Holder<F> {
+            effect: Eff<F>,
+        }
+
+        fn answer(base: i32) -> Eff<impl FnOnce() -> i32> {
+            effect_map!(as Eff: base + 2)
+        }
+
+        let holder = Holder {
+            effect: answer(40),
+        };
+        assert_eq!(holder.effect.bind(|a| move || a * 2)(), 84);
+    }
+
+    #[test]
+    fn effect_map_as_accepts_a_path() {
+        let effect: Eff<_> = effect_map!(as super::Eff: 6 * 7);
+        assert_eq!(effect(), 42);
+        let effect: Eff<_> = effect_map!(as ::Eff: 6 * 7);
+        assert_eq!(effect(), 42);
+    }
+
+    #[test]
+    fn apply_fn_effects_compose() {
+        use core::cell::Cell;
+
+        fn increment(c: &Cell<i32>) -> i32 {
+            c.set(c.get() + 1);
+            c.get()
+        }
+
+        fn double(c: &Cell<i32>) -> i32 {
+            c.set(c.get() * 2);
+            c.get()
+        }
+
+        let c = Cell::new(0);
+        let result = apply_fn(increment, &c).bind_ignore_contents(apply_fn(double, &c))();
+        assert_eq!(result, 2);
+        assert_eq!(c.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn apply_fn_runs_before_bound_effect() {
+        let tracker = testing::SeqTracker::new();
+        let chain = tracker.track(1, apply_fn(|a: i32| a + 1, 1))
+            .bind_ignore_contents(tracker.track(2, || 5));
+        assert_eq!(chain(), 5);
+        tracker.assert_order(&[1, 2]);
+    }
+
+    #[test]
+    fn effect_map_mut_is_repeatable() {
+        let mut count = 0;
+        {
+            let mut increment = effect_map!(mut {
+                count += 1;
+                count
+            });
+            increment();
+            increment();
+            assert_eq!(increment(), 3);
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn once_iter_yields_once_lazily() {
+        use core::cell::Cell;
+
+        let runs = Cell::new(0);
+        let mut iter = (|| {
+            runs.set(runs.get() + 1);
+            7
+        }).once_iter();
+        assert_eq!(runs.get(), 0);
+        assert_eq!(iter.next(), Some(7));
+        assert_eq!(iter.next(), None);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn tee_passes_clone_to_sink() {
+        use core::cell::Cell;
+
+        let seen = Cell::new(0);
+        let result = (|| 5).tee(|a| seen.set(a)).bind(|a| move || a + 1)();
+        assert_eq!(result, 6);
+        assert_eq!(seen.get(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn broadcast_feeds_every_sink() {
+        use core::cell::RefCell;
+
+        let received = RefCell::new(std::vec::Vec::new());
+        let sink = |tag: char| {
+            let received = &received;
+            move |a: i32| received.borrow_mut().push((tag, a))
+        };
+        let result = (|| 7).broadcast(vec![sink('a'), sink('b'), sink('c')])();
+        assert_eq!(result, 7);
+        assert_eq!(received.into_inner(), vec![('a', 7), ('b', 7), ('c', 7)]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn tee_sender_sends_clone() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        assert_eq!((|| 9).tee_sender(tx)(), 9);
+        assert_eq!(rx.recv(), Ok(9));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn flat_map_iter_runs_effect_per_item() {
+        use core::cell::Cell;
+
+        let runs = Cell::new(0);
+        let results = (|| vec![1, 2, 3]).flat_map_iter(|i: i32| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                i * 10
+            }
+        })();
+        assert_eq!(results, vec![10, 20, 30]);
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn then_run_runs_after_and_keeps_value() {
+        use core::cell::Cell;
+
+        let order = Cell::new(0);
+        let result = (|| {
+            order.set(order.get() * 10 + 1);
+            "value"
+        }).then_run(|| order.set(order.get() * 10 + 2))();
+        assert_eq!(result, "value");
+        assert_eq!(order.get(), 12);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn then_run_runs_after_effect_in_order() {
+        let tracker = testing::SeqTracker::new();
+        let result = tracker.track(1, || "value")
+            .then_run(|| { tracker.record(2); })();
+        assert_eq!(result, "value");
+        tracker.assert_order(&[1, 2]);
+    }
+
+    #[test]
+    fn prepend_runs_before_first() {
+        use core::cell::Cell;
+
+        let order = Cell::new(0);
+        let result = (|| {
+            order.set(order.get() * 10 + 2);
+            "value"
+        }).prepend(|| order.set(order.get() * 10 + 1))();
+        assert_eq!(result, "value");
+        assert_eq!(order.get(), 12);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn prepend_runs_before_effect_in_order() {
+        let tracker = testing::SeqTracker::new();
+        let result = tracker.track(2, || "value")
+            .prepend(|| { tracker.record(1); })();
+        assert_eq!(result, "value");
+        tracker.assert_order(&[1, 2]);
+    }
+
+    #[test]
+    fn fuse_runs_only_once() {
+        use core::cell::Cell;
+
+        let runs = Cell::new(0);
+        let mut fused = (|| {
+            runs.set(runs.get() + 1);
+            runs.get()
+        }).fuse();
+        assert_eq!(fused(), Some(1));
+        assert_eq!(fused(), None);
+        assert_eq!(fused(), None);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn bind_if_runs_when_predicate_passes() {
+        assert_eq!((|| 2).bind_if(|a| *a > 0, |a| move || a * 2)(), Some(4));
+    }
+
+    #[test]
+    fn bind_if_skips_when_predicate_fails() {
+        use core::cell::Cell;
+
+        let ran = Cell::new(false);
+        let result = (|| -2).bind_if(|a| *a > 0, |a| {
+            let ran = &ran;
+            move || {
+                ran.set(true);
+                a * 2
+            }
+        })();
+        assert_eq!(result, None);
+        assert!(!ran.get());
+    }
+
+    #[test]
+    fn hooked_runs_hooks_around_effect() {
+        use core::cell::Cell;
+
+        let order = Cell::new(0);
+        let seen = Cell::new(0);
+        let result = (|| {
+            order.set(order.get() * 10 + 2);
+            5
+        }).hooked(|| order.set(order.get() * 10 + 1), |a: &i32| {
+            order.set(order.get() * 10 + 3);
+            seen.set(*a);
+        })();
+        assert_eq!(result, 5);
+        assert_eq!(order.get(), 123);
+        assert_eq!(seen.get(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn hooked_runs_pre_effect_post_in_order() {
+        let tracker = testing::SeqTracker::new();
+        let result = tracker.track(2, || 5)
+            .hooked(|| { tracker.record(1); }, |_| { tracker.record(3); })();
+        assert_eq!(result, 5);
+        tracker.assert_order(&[1, 2, 3]);
+    }
+
+    #[test]
+    fn as_unit_wraps_and_runs() {
+        use core::cell::Cell;
+
+        fn run_side_effect<E: FnOnce()>(e: UnitEffect<E>) {
+            e()
+        }
+
+        let ran = Cell::new(false);
+        run_side_effect((|| ran.set(true)).as_unit());
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn infallible_always_ok() {
+        let result: Result<i32, core::convert::Infallible> = (|| 3).infallible()();
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test]
+    fn infallible_composes_with_fallible_effects() {
+        let result = result::zip_with_result((|| 2).infallible(), || Ok(3), |a, b| a + b)();
+        assert_eq!(result, Ok(5));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn trace_into_logs_each_stage() {
+        use core::cell::RefCell;
+
+        let log = RefCell::new(std::vec::Vec::new());
+        let result = (|| 1)
+            .trace_into(|a| log.borrow_mut().push(a))
+            .bind(|a| move || a + 1)
+            .trace_into(|a| log.borrow_mut().push(a))();
+        assert_eq!(result, 2);
+        assert_eq!(log.into_inner(), vec![1, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_local_overrides_and_restores() {
+        use core::cell::RefCell;
+
+        thread_local! {
+            static GREETING: RefCell<&'static str> = const { RefCell::new("hello") };
+        }
+
+        let seen = with_local!(GREETING = "howdy" => || GREETING.with_borrow(|g| *g))();
+        assert_eq!(seen, "howdy");
+        assert_eq!(GREETING.with_borrow(|g| *g), "hello");
+
+        let result = std::panic::catch_unwind(|| {
+            with_local!(GREETING = "howdy" => || -> () { panic!("inside with_local") })()
+        });
+        assert!(result.is_err());
+        assert_eq!(GREETING.with_borrow(|g| *g), "hello");
+    }
+
+    #[test]
+    fn compose_n_threads_value_through_each_run() {
+        use core::cell::Cell;
+
+        let runs = Cell::new(0);
+        let increment = |a: i32| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                a + 1
+            }
+        };
+        let effect = compose_n(5, increment)(0);
+        assert_eq!(runs.get(), 0);
+        assert_eq!(effect(), 5);
+        assert_eq!(runs.get(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_correlation_id_is_visible_to_hooks() {
+        use core::cell::RefCell;
+
+        let seen = RefCell::new(std::vec::Vec::new());
+        let result = (|| correlation_id())
+            .hooked(|| seen.borrow_mut().push(correlation_id()), |_| seen.borrow_mut().push(correlation_id()))
+            .trace_into(|id| seen.borrow_mut().push(id))
+            .with_correlation_id(17)();
+        assert_eq!(result, Some(17));
+        assert_eq!(seen.into_inner(), vec![Some(17); 3]);
+        assert_eq!(correlation_id(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_correlation_id_nests() {
+        let inner = (|| correlation_id()).with_correlation_id(2);
+        let result = (move || (correlation_id(), inner(), correlation_id())).with_correlation_id(1)();
+        assert_eq!(result, (Some(1), Some(2), Some(1)));
+    }
+
+    #[test]
+    fn fn_ptr_effects_compose() {
+        let chain = from_fn_ptr(|| 2).bind_ignore_contents(from_fn_ptr(|| 3));
+        assert_eq!(chain(), 3);
+    }
+
+    #[test]
+    fn fn_ptr_chains_share_a_type() {
+        use core::mem::{size_of, size_of_val};
+
+        // Two chains of different closures only fit in one array because
+        // from_fn_ptr gives them the same type
+        let chains = [
+            from_fn_ptr(|| 1).bind_ignore_contents(from_fn_ptr(|| 2)),
+            from_fn_ptr(|| 3).bind_ignore_contents(from_fn_ptr(|| 4)),
+        ];
+        let closures = (|| 1).bind_ignore_contents(|| 2);
+        assert_eq!(size_of_val(&closures), 0);
+        assert_eq!(size_of_val(&chains[0]), 2 * size_of::<fn() -> i32>());
+        assert_eq!(closures(), 2);
+        let [a, b] = chains;
+        assert_eq!((a(), b()), (2, 4));
+    }
+
+    #[test]
+    fn bind_closure_chains_value() {
+        let offset = 10;
+        let chain = bind_closure!(|| 1, a => a + offset);
+        assert_eq!(chain(), 11);
+    }
+
+    #[test]
+    fn bind_closure_nests() {
+        let chain = bind_closure!(bind_closure!(|| 2, a => a * 3), b => b + 1);
+        assert_eq!(chain(), 7);
+    }
+
+    #[test]
+    fn keep_input_pairs_original_and_transformed() {
+        assert_eq!((|| 4).keep_input(|a| a * a)(), (4, 16));
+    }
+
+    #[test]
+    fn count_runs_counts_each_call() {
+        let counted = (|| 7).count_runs();
+        assert_eq!(counted.count(), 0);
+        let results = [counted(), counted(), counted()];
+        assert_eq!(results, [7, 7, 7]);
+        assert_eq!(counted.count(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn count_runs_counts_retries() {
+        use core::time::Duration;
+        use retry::RetryEffect;
+
+        let counted = (|| Err::<(), _>("failed")).count_runs();
+        let policy = retry::ConstantBackoff::new(Duration::from_millis(1), 2);
+        assert_eq!((&counted).retry_policy(policy)(), Err("failed"));
+        assert_eq!(counted.count(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_env_sets_and_restores_variable() {
+        const KEY: &str = "EFFECT_MONAD_WITH_ENV_TEST";
+
+        std::env::remove_var(KEY);
+        let inside = (|| std::env::var(KEY)).with_env(KEY, "inner")();
+        assert_eq!(inside.as_deref(), Ok("inner"));
+        assert!(std::env::var_os(KEY).is_none());
+
+        std::env::set_var(KEY, "outer");
+        let inside = (|| std::env::var(KEY)).with_env(KEY, "inner")();
+        assert_eq!(inside.as_deref(), Ok("inner"));
+        assert_eq!(std::env::var(KEY).as_deref(), Ok("outer"));
+        std::env::remove_var(KEY);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_env_restores_variable_on_panic() {
+        const KEY: &str = "EFFECT_MONAD_WITH_ENV_PANIC_TEST";
+
+        let result = std::panic::catch_unwind(|| {
+            (|| -> () { panic!("inside with_env") }).with_env(KEY, "set")()
+        });
+        assert!(result.is_err());
+        assert!(std::env::var_os(KEY).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "a fence can't have Relaxed ordering")]
+    fn with_fence_rejects_relaxed_before_running() {
+        (|| -> () { panic!("the effect shouldn't run") }).with_fence(Ordering::Relaxed);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn with_fence_publishes_relaxed_writes() {
+        use core::sync::atomic::AtomicBool;
+
+        let data = AtomicUsize::new(0);
+        let ready = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                (|| data.store(42, Ordering::Relaxed)).with_fence(Ordering::Release)();
+                ready.store(true, Ordering::Relaxed);
+            });
+            while !ready.load(Ordering::Relaxed) {
+                std::hint::spin_loop();
+            }
+            core::sync::atomic::fence(Ordering::Acquire);
+            assert_eq!(data.load(Ordering::Relaxed), 42);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn bind_any_stops_at_first_ok() {
+        use core::cell::RefCell;
+
+        let tried = RefCell::new(std::vec::Vec::new());
+        let attempt = |tag: usize, ok: bool| {
+            let tried = &tried;
+            move |a: usize| move || {
+                tried.borrow_mut().push(tag);
+                if ok { Ok(a + tag) } else { Err(tag) }
+            }
+        };
+        let result = (|| 10).bind_any(vec![attempt(1, false), attempt(2, true), attempt(3, true)])();
+        assert_eq!(result, Ok(12));
+        assert_eq!(tried.into_inner(), vec![1, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn bind_any_collects_every_error() {
+        let fail = |tag: i32| move |_: i32| move || Err::<(), _>(tag);
+        assert_eq!((|| 0).bind_any(vec![fail(1), fail(2)])(), Err(vec![1, 2]));
+    }
+
+    #[test]
+    fn gated_skips_effect_when_gate_is_closed() {
+        use core::cell::Cell;
+
+        let enabled = Cell::new(false);
+        let ran = Cell::new(0);
+        let effect = || {
+            ran.set(ran.get() + 1);
+            5
+        };
+        assert_eq!(effect.gated(|| enabled.get())(), None);
+        assert_eq!(ran.get(), 0);
+        enabled.set(true);
+        assert_eq!(effect.gated(|| enabled.get())(), Some(5));
+        assert_eq!(ran.get(), 1);
+    }
+
+    #[test]
+    fn and_short_circuits_on_false() {
+        use core::cell::Cell;
+
+        let ran = Cell::new(false);
+        assert!(!(|| false).and(|| {
+            ran.set(true);
+            true
+        })());
+        assert!(!ran.get());
+        assert!((|| true).and(|| {
+            ran.set(true);
+            true
+        })());
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn or_short_circuits_on_true() {
+        use core::cell::Cell;
+
+        let ran = Cell::new(false);
+        assert!((|| true).or(|| {
+            ran.set(true);
+            false
+        })());
+        assert!(!ran.get());
+        assert!(!(|| false).or(|| {
+            ran.set(true);
+            false
+        })());
+        assert!(ran.get());
+    }
 }
 
 // It's OK for the code in the following tests to be "unsafe" becuase we know
@@ -0,0 +1,86 @@
+//! A pluggable hook for collecting metrics about effects.
+
+use core::convert::TryFrom;
+use std::time::Instant;
+
+/// A sink for metrics, implemented by the user to forward them to whichever
+/// metrics library they use.
+pub trait Metrics {
+    /// Increments the counter `name` by one.
+    fn incr(&self, name: &'static str);
+
+    /// Records `value` as a sample of `name`.
+    fn record(&self, name: &'static str, value: u64);
+}
+
+impl<M: Metrics + ?Sized> Metrics for &M {
+    fn incr(&self, name: &'static str) {
+        (**self).incr(name)
+    }
+
+    fn record(&self, name: &'static str, value: u64) {
+        (**self).record(name, value)
+    }
+}
+
+/// Effect returned by `EffectMonad::metered`.
+pub struct MeteredEffect<Ea, M> {
+    pub(crate) e: Ea,
+    pub(crate) metrics: M,
+    pub(crate) name: &'static str,
+}
+
+impl<A, Ea, M> FnOnce<()> for MeteredEffect<Ea, M>
+    where Ea: FnOnce() -> A,
+          M: Metrics,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        self.metrics.incr(self.name);
+        let start = Instant::now();
+        let a = (self.e)();
+        let micros = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.metrics.record(self.name, micros);
+        a
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::RefCell;
+    use core::time::Duration;
+    use std::vec::Vec;
+    use EffectMonad;
+
+    #[derive(Default)]
+    struct MockMetrics {
+        counters: RefCell<Vec<&'static str>>,
+        samples: RefCell<Vec<(&'static str, u64)>>,
+    }
+
+    impl Metrics for MockMetrics {
+        fn incr(&self, name: &'static str) {
+            self.counters.borrow_mut().push(name);
+        }
+
+        fn record(&self, name: &'static str, value: u64) {
+            self.samples.borrow_mut().push((name, value));
+        }
+    }
+
+    #[test]
+    fn metered_counts_and_records_duration() {
+        let metrics = MockMetrics::default();
+        let result = (|| {
+            std::thread::sleep(Duration::from_millis(5));
+            3
+        }).metered(&metrics, "sleepy")();
+        assert_eq!(result, 3);
+        assert_eq!(metrics.counters.into_inner(), vec!["sleepy"]);
+        let samples = metrics.samples.into_inner();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].0, "sleepy");
+        assert!(samples[0].1 >= 5_000);
+    }
+}
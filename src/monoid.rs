@@ -0,0 +1,93 @@
+//! Algebraic structures for combining effect results.
+
+use core::ops::Add;
+
+/// A type with an associative binary operation.
+pub trait Semigroup {
+    /// Combines `self` with `other`. `self` is always the left operand.
+    fn combine(self, other: Self) -> Self;
+}
+
+/// A semigroup with an identity element.
+pub trait Monoid: Semigroup {
+    /// The identity element, for which `x.combine(empty())` is `x`.
+    fn empty() -> Self;
+}
+
+/// Wrapper combining numbers by addition.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sum<T>(pub T);
+
+impl<T: Add<Output = T>> Semigroup for Sum<T> {
+    fn combine(self, other: Self) -> Self {
+        Sum(self.0 + other.0)
+    }
+}
+
+impl<T: Add<Output = T> + Default> Monoid for Sum<T> {
+    fn empty() -> Self {
+        Sum(T::default())
+    }
+}
+
+impl Semigroup for () {
+    fn combine(self, _: Self) -> Self {}
+}
+
+impl Monoid for () {
+    fn empty() -> Self {}
+}
+
+#[cfg(feature = "alloc")]
+impl Semigroup for alloc::string::String {
+    fn combine(mut self, other: Self) -> Self {
+        self.push_str(&other);
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Monoid for alloc::string::String {
+    fn empty() -> Self {
+        alloc::string::String::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Semigroup for alloc::vec::Vec<T> {
+    fn combine(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Monoid for alloc::vec::Vec<T> {
+    fn empty() -> Self {
+        alloc::vec::Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use EffectMonad;
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn combine_strings_left_to_right() {
+        use std::string::ToString;
+
+        assert_eq!((|| "a".to_string()).combine(|| "b".to_string())(), "ab");
+    }
+
+    #[test]
+    fn combine_sums() {
+        assert_eq!((|| Sum(2)).combine(|| Sum(3))(), Sum(5));
+    }
+
+    #[test]
+    fn sum_empty_is_identity() {
+        assert_eq!(Sum(4).combine(Sum::empty()), Sum(4));
+    }
+}
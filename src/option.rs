@@ -0,0 +1,103 @@
+//! Combinators for effects which produce an `Option`.
+
+/// Extension trait for effects of the form `FnOnce() -> Option<A>`.
+pub trait OptionEffect<A>: Sized {
+    /// Runs the effect and, on `Some(a)`, applies `f` to `a`, otherwise runs
+    /// the `default` effect. Exactly one of `f` and `default` is run.
+    #[inline(always)]
+    fn map_or_else_effect<B, Ed, F>(self, default: Ed, f: F) -> MapOrElseEffect<Self, Ed, F>
+        where Ed: FnOnce() -> B,
+              F: FnOnce(A) -> B,
+    {
+        MapOrElseEffect {
+            e: self,
+            default,
+            f,
+        }
+    }
+
+    /// Converts the effect into one producing `Result<A, E>`, with `err` in
+    /// place of `None`, like `Option::ok_or`.
+    #[inline(always)]
+    fn ok_or<E>(self, err: E) -> OkOrEffect<Self, E> {
+        OkOrEffect {
+            e: self,
+            err,
+        }
+    }
+}
+
+impl<T, A> OptionEffect<A> for T
+    where T: FnOnce() -> Option<A>,
+{
+}
+
+/// Effect returned by `OptionEffect::map_or_else_effect`.
+pub struct MapOrElseEffect<E, Ed, F> {
+    e: E,
+    default: Ed,
+    f: F,
+}
+
+impl<A, B, E, Ed, F> FnOnce<()> for MapOrElseEffect<E, Ed, F>
+    where E: FnOnce() -> Option<A>,
+          Ed: FnOnce() -> B,
+          F: FnOnce(A) -> B,
+{
+    type Output = B;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        match (self.e)() {
+            Some(a) => (self.f)(a),
+            None => (self.default)(),
+        }
+    }
+}
+
+/// Effect returned by `OptionEffect::ok_or`.
+pub struct OkOrEffect<Ea, E> {
+    e: Ea,
+    err: E,
+}
+
+impl<A, E, Ea> FnOnce<()> for OkOrEffect<Ea, E>
+    where Ea: FnOnce() -> Option<A>,
+{
+    type Output = Result<A, E>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)().ok_or(self.err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn map_or_else_effect_maps_some() {
+        let defaulted = Cell::new(false);
+        let result = (|| Some(2)).map_or_else_effect(|| {
+            defaulted.set(true);
+            0
+        }, |a| a * 10)();
+        assert_eq!(result, 20);
+        assert!(!defaulted.get());
+    }
+
+    #[test]
+    fn map_or_else_effect_defaults_none() {
+        let mapped = Cell::new(false);
+        let result = (|| None).map_or_else_effect(|| 7, |a: i32| {
+            mapped.set(true);
+            a
+        })();
+        assert_eq!(result, 7);
+        assert!(!mapped.get());
+    }
+
+    #[test]
+    fn ok_or_converts_some_and_none() {
+        assert_eq!((|| Some(1)).ok_or("missing")(), Ok(1));
+        assert_eq!((|| None::<i32>).ok_or("missing")(), Err("missing"));
+    }
+}
@@ -0,0 +1,205 @@
+//! Combinators for effects which may panic.
+
+use core::any::Any;
+use std::boxed::Box;
+use std::panic::{self, PanicHookInfo, UnwindSafe};
+use std::string::{String, ToString};
+
+/// Extension trait for effects which may panic.
+pub trait PanicEffect<A>: Sized {
+    /// Runs the effect, catching any unwinding panic and converting its
+    /// payload into an error with `f`.
+    #[inline(always)]
+    fn map_panic<E, F>(self, f: F) -> MapPanicEffect<Self, F>
+        where F: FnOnce(Box<dyn Any + Send>) -> E,
+    {
+        MapPanicEffect {
+            e: self,
+            f,
+        }
+    }
+
+    /// Runs the effect, returning `default` if it panics.
+    ///
+    /// This isn't error handling: the panic is caught and discarded, and no
+    /// `Result` is exposed. Only unwinding panics can be caught, so this has
+    /// no effect when panics abort.
+    #[inline(always)]
+    fn ignore_panic(self, default: A) -> IgnorePanicEffect<Self, A> {
+        IgnorePanicEffect {
+            e: self,
+            default,
+        }
+    }
+
+    /// Runs the effect with `hook` installed as the panic hook, catching any
+    /// unwinding panic and returning its payload as the error. The previous
+    /// hook is restored afterwards, whether or not the effect panicked.
+    ///
+    /// The panic hook is process-global, so while the effect runs, `hook`
+    /// also sees panics from every other thread, and installing hooks from
+    /// several threads at once may not restore the original.
+    #[inline(always)]
+    fn with_panic_hook<H>(self, hook: H) -> WithPanicHookEffect<Self, H>
+        where H: Fn(&PanicHookInfo) + Send + Sync + 'static,
+    {
+        WithPanicHookEffect {
+            e: self,
+            hook,
+        }
+    }
+}
+
+impl<T, A> PanicEffect<A> for T
+    where T: FnOnce() -> A + UnwindSafe,
+{
+}
+
+/// Effect returned by `PanicEffect::map_panic`.
+pub struct MapPanicEffect<Ea, F> {
+    e: Ea,
+    f: F,
+}
+
+impl<A, E, Ea, F> FnOnce<()> for MapPanicEffect<Ea, F>
+    where Ea: FnOnce() -> A + UnwindSafe,
+          F: FnOnce(Box<dyn Any + Send>) -> E,
+{
+    type Output = Result<A, E>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        panic::catch_unwind(self.e).map_err(self.f)
+    }
+}
+
+/// Effect returned by `PanicEffect::ignore_panic`.
+pub struct IgnorePanicEffect<Ea, A> {
+    e: Ea,
+    default: A,
+}
+
+impl<A, Ea> FnOnce<()> for IgnorePanicEffect<Ea, A>
+    where Ea: FnOnce() -> A + UnwindSafe,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        panic::catch_unwind(self.e).unwrap_or(self.default)
+    }
+}
+
+/// Effect returned by `PanicEffect::with_panic_hook`.
+pub struct WithPanicHookEffect<Ea, H> {
+    e: Ea,
+    hook: H,
+}
+
+impl<A, Ea, H> FnOnce<()> for WithPanicHookEffect<Ea, H>
+    where Ea: FnOnce() -> A + UnwindSafe,
+          H: Fn(&PanicHookInfo) + Send + Sync + 'static,
+{
+    type Output = Result<A, Box<dyn Any + Send>>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(self.hook));
+        let result = panic::catch_unwind(self.e);
+        // Drop our hook before putting the previous one back
+        let _ = panic::take_hook();
+        panic::set_hook(previous);
+        result
+    }
+}
+
+/// Extracts a readable message from a panic payload.
+///
+/// Payloads created by `panic!` with a message are either a `&'static str` or
+/// a `String`; anything else yields `"unknown panic"`.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Creates an effect which runs `e`, catching any unwinding panic and
+/// returning its message as the error.
+///
+/// This is `std::panic::catch_unwind` with the payload converted by
+/// `panic_message`, which makes caught panics useful in logs.
+pub fn catch_unwind_msg<A, E>(e: E) -> impl FnOnce() -> Result<A, String>
+    where E: FnOnce() -> A + UnwindSafe,
+{
+    move || panic::catch_unwind(e).map_err(|payload: Box<dyn Any + Send>| panic_message(&*payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn catch_unwind_msg_extracts_str_message() {
+        let result: Result<(), _> = catch_unwind_msg(|| panic!("boom"))();
+        assert!(result.unwrap_err().contains("boom"));
+    }
+
+    #[test]
+    fn catch_unwind_msg_extracts_formatted_message() {
+        let code = 7;
+        let result: Result<(), _> = catch_unwind_msg(move || panic!("failed with {}", code))();
+        assert_eq!(result, Err(format!("failed with {}", code)));
+    }
+
+    #[test]
+    fn catch_unwind_msg_passes_value() {
+        assert_eq!(catch_unwind_msg(|| 3)(), Ok(3));
+    }
+
+    #[test]
+    fn panic_message_falls_back_to_unknown() {
+        assert_eq!(panic_message(&5), "unknown panic");
+    }
+
+    #[test]
+    fn map_panic_routes_payload_through_f() {
+        #[derive(Debug, PartialEq)]
+        struct Crashed(String);
+
+        let result: Result<(), _> = (|| panic!("kaboom")).map_panic(|p| Crashed(panic_message(&*p)))();
+        assert_eq!(result, Err(Crashed("kaboom".to_string())));
+    }
+
+    #[test]
+    fn map_panic_passes_value() {
+        let result = (|| 1).map_panic(|_| "panicked")();
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn ignore_panic_returns_default() {
+        assert_eq!((|| -> i32 { panic!("ignored") }).ignore_panic(-1)(), -1);
+    }
+
+    #[test]
+    fn ignore_panic_returns_value() {
+        assert_eq!((|| 2).ignore_panic(-1)(), 2);
+    }
+
+    #[test]
+    fn with_panic_hook_installs_and_restores_hook() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let hook_calls = calls.clone();
+        let result: Result<(), _> = (|| panic!("hooked")).with_panic_hook(move |_| {
+            hook_calls.fetch_add(1, Ordering::SeqCst);
+        })();
+        assert!(result.is_err());
+        let after = calls.load(Ordering::SeqCst);
+        assert!(after >= 1);
+
+        assert!(panic::catch_unwind(|| panic!("unhooked")).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), after);
+    }
+}
@@ -0,0 +1,304 @@
+//! Combinators for fallible effects, which produce a `Result`.
+
+use core::fmt;
+
+/// Extension trait for effects of the form `FnOnce() -> Result<A, E>`.
+pub trait ResultEffect<A, E>: Sized {
+    /// Runs the effect and, on `Err(e)`, runs the recovery effect produced by
+    /// `f(e)`. An `Ok` result is passed through without running `f`.
+    #[inline(always)]
+    fn or_else_effect<E2, Er, Ef>(self, f: Ef) -> OrElseEffect<Self, Ef>
+        where Er: FnOnce() -> Result<A, E2>,
+              Ef: FnOnce(E) -> Er,
+    {
+        OrElseEffect {
+            e: self,
+            f,
+        }
+    }
+
+    /// Runs the effect, then runs the effect produced by `on_ok` or `on_err`,
+    /// whichever matches its result, returning that effect's result. Only
+    /// the matching branch is run.
+    #[inline(always)]
+    fn branch_result<B, FOk, FErr, EOk, EErr>(self, on_ok: FOk, on_err: FErr) -> BranchResultEffect<Self, FOk, FErr>
+        where FOk: FnOnce(A) -> EOk,
+              FErr: FnOnce(E) -> EErr,
+              EOk: FnOnce() -> B,
+              EErr: FnOnce() -> B,
+    {
+        BranchResultEffect {
+            e: self,
+            on_ok,
+            on_err,
+        }
+    }
+
+    /// Collapses an effect producing `Result<Result<A, E>, E>` into one
+    /// producing `Result<A, E>`, mirroring `Result::flatten`.
+    #[inline(always)]
+    fn flatten_result(self) -> FlattenResultEffect<Self> {
+        FlattenResultEffect {
+            e: self,
+        }
+    }
+
+    /// Runs the effect and unwraps its result, panicking with `msg` and the
+    /// error if it failed. Meant for top-level code, like `Result::expect`.
+    #[inline(always)]
+    fn expect(self, msg: &str) -> ExpectEffect<'_, Self>
+        where E: fmt::Debug,
+    {
+        ExpectEffect {
+            e: self,
+            msg,
+        }
+    }
+
+    /// Runs the effect and unwraps its result, panicking with the error if it
+    /// failed, like `Result::unwrap`.
+    #[inline(always)]
+    fn unwrap(self) -> UnwrapEffect<Self>
+        where E: fmt::Debug,
+    {
+        UnwrapEffect {
+            e: self,
+        }
+    }
+
+    /// Converts the effect into one producing `Option<A>`, discarding the
+    /// error, like `Result::ok`.
+    #[inline(always)]
+    fn ok(self) -> OkEffect<Self> {
+        OkEffect {
+            e: self,
+        }
+    }
+}
+
+impl<T, A, E> ResultEffect<A, E> for T
+    where T: FnOnce() -> Result<A, E>,
+{
+}
+
+/// Effect returned by `ResultEffect::or_else_effect`.
+pub struct OrElseEffect<Ea, F> {
+    e: Ea,
+    f: F,
+}
+
+impl<A, E, E2, Ea, Er, F> FnOnce<()> for OrElseEffect<Ea, F>
+    where Ea: FnOnce() -> Result<A, E>,
+          Er: FnOnce() -> Result<A, E2>,
+          F: FnOnce(E) -> Er,
+{
+    type Output = Result<A, E2>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        match (self.e)() {
+            Ok(a) => Ok(a),
+            Err(e) => (self.f)(e)(),
+        }
+    }
+}
+
+/// Effect returned by `ResultEffect::branch_result`.
+pub struct BranchResultEffect<Ea, FOk, FErr> {
+    e: Ea,
+    on_ok: FOk,
+    on_err: FErr,
+}
+
+impl<A, B, E, Ea, FOk, FErr, EOk, EErr> FnOnce<()> for BranchResultEffect<Ea, FOk, FErr>
+    where Ea: FnOnce() -> Result<A, E>,
+          FOk: FnOnce(A) -> EOk,
+          FErr: FnOnce(E) -> EErr,
+          EOk: FnOnce() -> B,
+          EErr: FnOnce() -> B,
+{
+    type Output = B;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        match (self.e)() {
+            Ok(a) => (self.on_ok)(a)(),
+            Err(e) => (self.on_err)(e)(),
+        }
+    }
+}
+
+/// Effect returned by `ResultEffect::flatten_result`.
+pub struct FlattenResultEffect<Ea> {
+    e: Ea,
+}
+
+impl<A, E, Ea> FnOnce<()> for FlattenResultEffect<Ea>
+    where Ea: FnOnce() -> Result<Result<A, E>, E>,
+{
+    type Output = Result<A, E>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)().and_then(|inner| inner)
+    }
+}
+
+/// Effect returned by `ResultEffect::expect`.
+pub struct ExpectEffect<'a, Ea> {
+    e: Ea,
+    msg: &'a str,
+}
+
+impl<'a, A, E, Ea> FnOnce<()> for ExpectEffect<'a, Ea>
+    where Ea: FnOnce() -> Result<A, E>,
+          E: fmt::Debug,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)().expect(self.msg)
+    }
+}
+
+/// Effect returned by `ResultEffect::unwrap`.
+pub struct UnwrapEffect<Ea> {
+    e: Ea,
+}
+
+impl<A, E, Ea> FnOnce<()> for UnwrapEffect<Ea>
+    where Ea: FnOnce() -> Result<A, E>,
+          E: fmt::Debug,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)().unwrap()
+    }
+}
+
+/// Effect returned by `ResultEffect::ok`.
+pub struct OkEffect<Ea> {
+    e: Ea,
+}
+
+impl<A, E, Ea> FnOnce<()> for OkEffect<Ea>
+    where Ea: FnOnce() -> Result<A, E>,
+{
+    type Output = Option<A>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        (self.e)().ok()
+    }
+}
+
+/// Creates an effect which runs `ea` then `eb`, combining their `Ok` values
+/// with `f`.
+///
+/// If `ea` fails, `eb` is never run and the error is returned.
+pub fn zip_with_result<A, B, C, E, Ea, Eb, F>(ea: Ea, eb: Eb, f: F) -> impl FnOnce() -> Result<C, E>
+    where Ea: FnOnce() -> Result<A, E>,
+          Eb: FnOnce() -> Result<B, E>,
+          F: FnOnce(A, B) -> C,
+{
+    move || {
+        let a = ea()?;
+        let b = eb()?;
+        Ok(f(a, b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn or_else_effect_recovers() {
+        let result = (|| Err::<i32, _>("boom")).or_else_effect(|e: &str| move || Ok::<_, ()>(e.len() as i32))();
+        assert_eq!(result, Ok(4));
+    }
+
+    #[test]
+    fn or_else_effect_skips_recovery_on_ok() {
+        let recovered = Cell::new(false);
+        let result = (|| Ok::<_, ()>(1)).or_else_effect(|_| || {
+            recovered.set(true);
+            Ok::<_, ()>(2)
+        })();
+        assert_eq!(result, Ok(1));
+        assert!(!recovered.get());
+    }
+
+    #[test]
+    fn zip_with_result_combines_oks() {
+        let result = zip_with_result(|| Ok::<_, ()>(2), || Ok(3), |a, b| a * b)();
+        assert_eq!(result, Ok(6));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn zip_with_result_runs_left_then_right() {
+        let tracker = ::testing::SeqTracker::new();
+        let result = zip_with_result(
+            tracker.track(1, || Ok::<_, ()>(2)),
+            tracker.track(2, || Ok(3)),
+            |a, b| a * b,
+        )();
+        assert_eq!(result, Ok(6));
+        tracker.assert_order(&[1, 2]);
+    }
+
+    #[test]
+    fn zip_with_result_skips_second_on_err() {
+        let ran = Cell::new(false);
+        let result = zip_with_result(|| Err::<i32, _>("first"), || {
+            ran.set(true);
+            Ok(1)
+        }, |a, b: i32| a + b)();
+        assert_eq!(result, Err("first"));
+        assert!(!ran.get());
+    }
+
+    #[test]
+    fn flatten_result_collapses_nesting() {
+        assert_eq!((|| Ok::<Result<i32, &str>, &str>(Ok(1))).flatten_result()(), Ok(1));
+        assert_eq!((|| Ok::<Result<i32, &str>, &str>(Err("inner"))).flatten_result()(), Err("inner"));
+        assert_eq!((|| Err::<Result<i32, &str>, &str>("outer")).flatten_result()(), Err("outer"));
+    }
+
+    #[test]
+    fn expect_returns_ok_value() {
+        assert_eq!((|| Ok::<_, ()>(1)).expect("should succeed")(), 1);
+        assert_eq!((|| Ok::<_, ()>(2)).unwrap()(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "loading config")]
+    fn expect_panics_with_message() {
+        (|| Err::<(), _>("missing")).expect("loading config")();
+    }
+
+    #[test]
+    #[should_panic(expected = "missing")]
+    fn unwrap_panics_with_error() {
+        (|| Err::<(), _>("missing")).unwrap()();
+    }
+
+    #[test]
+    fn ok_keeps_value_and_discards_error() {
+        assert_eq!((|| Ok::<_, &str>(1)).ok()(), Some(1));
+        assert_eq!((|| Err::<i32, _>("failed")).ok()(), None);
+    }
+
+    #[test]
+    fn branch_result_runs_only_matching_branch() {
+        let (oks, errs) = (Cell::new(0), Cell::new(0));
+        let branch = |result: Result<i32, &'static str>| {
+            let (oks, errs) = (&oks, &errs);
+            (move || result).branch_result(|a| move || {
+                oks.set(oks.get() + 1);
+                a * 2
+            }, |e: &str| move || {
+                errs.set(errs.get() + 1);
+                e.len() as i32
+            })()
+        };
+
+        assert_eq!(branch(Ok(4)), 8);
+        assert_eq!((oks.get(), errs.get()), (1, 0));
+        assert_eq!(branch(Err("bad")), 3);
+        assert_eq!((oks.get(), errs.get()), (1, 1));
+    }
+}
@@ -0,0 +1,224 @@
+//! Combinators for retrying fallible effects.
+
+use core::time::Duration;
+use std::thread;
+
+/// Decides whether, and after how long, a failed effect is retried.
+pub trait RetryPolicy {
+    /// Called after the `attempt`th failed attempt (counting from one).
+    /// Returns the delay before the next attempt, or `None` to give up.
+    fn next_delay(&mut self, attempt: usize) -> Option<Duration>;
+}
+
+/// Retries up to `retries` times, waiting the same `delay` before each retry.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantBackoff {
+    pub delay: Duration,
+    pub retries: usize,
+}
+
+impl ConstantBackoff {
+    /// Creates a policy retrying up to `retries` times, `delay` apart.
+    pub fn new(delay: Duration, retries: usize) -> Self {
+        ConstantBackoff {
+            delay,
+            retries,
+        }
+    }
+}
+
+impl RetryPolicy for ConstantBackoff {
+    fn next_delay(&mut self, attempt: usize) -> Option<Duration> {
+        if attempt <= self.retries { Some(self.delay) } else { None }
+    }
+}
+
+/// Retries up to `retries` times, waiting `base` before the first retry and
+/// doubling the wait before each one after it.
+#[derive(Clone, Copy, Debug)]
+pub struct ExponentialBackoff {
+    pub base: Duration,
+    pub retries: usize,
+}
+
+impl ExponentialBackoff {
+    /// Creates a policy retrying up to `retries` times, starting at `base`.
+    pub fn new(base: Duration, retries: usize) -> Self {
+        ExponentialBackoff {
+            base,
+            retries,
+        }
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn next_delay(&mut self, attempt: usize) -> Option<Duration> {
+        if attempt > self.retries {
+            return None;
+        }
+        let factor = 1u32.checked_shl((attempt as u32).saturating_sub(1)).unwrap_or(u32::MAX);
+        Some(self.base.saturating_mul(factor))
+    }
+}
+
+/// Extension trait for repeatable fallible effects, of the form
+/// `Fn() -> Result<A, E>`.
+pub trait RetryEffect<A, E>: Sized {
+    /// Runs the effect until it succeeds, retrying failures for as long as
+    /// `policy` allows and sleeping for the delay it asks for in between.
+    /// The last error is returned once the policy gives up.
+    #[inline(always)]
+    fn retry_policy<P>(self, policy: P) -> RetryPolicyEffect<Self, P>
+        where P: RetryPolicy,
+    {
+        RetryPolicyEffect {
+            e: self,
+            policy,
+        }
+    }
+}
+
+impl<T, A, E> RetryEffect<A, E> for T
+    where T: Fn() -> Result<A, E>,
+{
+}
+
+/// Effect returned by `RetryEffect::retry_policy`.
+pub struct RetryPolicyEffect<Ea, P> {
+    e: Ea,
+    policy: P,
+}
+
+impl<A, E, Ea, P> FnOnce<()> for RetryPolicyEffect<Ea, P>
+    where Ea: Fn() -> Result<A, E>,
+          P: RetryPolicy,
+{
+    type Output = Result<A, E>;
+    extern "rust-call" fn call_once(mut self, _: ()) -> Self::Output {
+        let mut attempt = 1;
+        loop {
+            let result = (self.e)();
+            if result.is_ok() {
+                return result;
+            }
+            match self.policy.next_delay(attempt) {
+                Some(delay) => thread::sleep(delay),
+                None => return result,
+            }
+            attempt += 1;
+        }
+    }
+}
+
+/// Creates an effect which runs `e` up to `attempts` times until it succeeds,
+/// sleeping between attempts.
+///
+/// The first retry waits `base`, and each following wait is twice the one
+/// before it. The error from the final attempt is returned if every attempt
+/// fails. `e` is always run at least once, even if `attempts` is zero.
+pub fn retry_backoff<A, E, Eff>(attempts: usize, base: Duration, e: Eff) -> impl FnOnce() -> Result<A, E>
+    where Eff: Fn() -> Result<A, E>,
+{
+    move || {
+        let mut delay = base;
+        let mut result = e();
+        for _ in 1..attempts {
+            if result.is_ok() {
+                break;
+            }
+            thread::sleep(delay);
+            delay = delay.saturating_mul(2);
+            result = e();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    fn fail_times(failures: usize, calls: &Cell<usize>) -> impl Fn() -> Result<usize, &'static str> + '_ {
+        move || {
+            calls.set(calls.get() + 1);
+            if calls.get() > failures { Ok(calls.get()) } else { Err("failed") }
+        }
+    }
+
+    #[test]
+    fn retry_backoff_retries_until_success() {
+        let calls = Cell::new(0);
+        let result = retry_backoff(5, Duration::from_millis(1), fail_times(2, &calls))();
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_backoff_gives_up_after_attempts() {
+        let calls = Cell::new(0);
+        let result = retry_backoff(3, Duration::from_millis(1), fail_times(10, &calls))();
+        assert_eq!(result, Err("failed"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_backoff_doubles_delay() {
+        use std::time::Instant;
+
+        let calls = Cell::new(0);
+        let start = Instant::now();
+        assert!(retry_backoff(3, Duration::from_millis(10), fail_times(10, &calls))().is_err());
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn retry_policy_retries_per_policy() {
+        let calls = Cell::new(0);
+        let result = fail_times(10, &calls).retry_policy(ConstantBackoff::new(Duration::from_millis(1), 3))();
+        assert_eq!(result, Err("failed"));
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn retry_policy_stops_on_success() {
+        let calls = Cell::new(0);
+        let result = fail_times(1, &calls).retry_policy(ExponentialBackoff::new(Duration::from_millis(1), 5))();
+        assert_eq!(result, Ok(2));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn retry_policy_uses_custom_policy() {
+        struct Never;
+
+        impl RetryPolicy for Never {
+            fn next_delay(&mut self, _: usize) -> Option<Duration> {
+                None
+            }
+        }
+
+        let calls = Cell::new(0);
+        assert!(fail_times(10, &calls).retry_policy(Never)().is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn exponential_backoff_treats_attempt_zero_as_first() {
+        let mut policy = ExponentialBackoff::new(Duration::from_millis(10), 3);
+        assert_eq!(policy.next_delay(0), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn exponential_backoff_doubles() {
+        let mut policy = ExponentialBackoff::new(Duration::from_millis(10), 3);
+        let delays: Vec<_> = (1..5).map(|attempt| policy.next_delay(attempt)).collect();
+        assert_eq!(delays, vec![
+            Some(Duration::from_millis(10)),
+            Some(Duration::from_millis(20)),
+            Some(Duration::from_millis(40)),
+            None,
+        ]);
+    }
+}
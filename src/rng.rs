@@ -0,0 +1,71 @@
+//! Seeded pseudo-random number generation, for making effects which use
+//! randomness reproducible.
+//!
+//! This is only available with the `rng` feature. The generator is a small
+//! xorshift, which is fast and dependency-free but not cryptographically
+//! secure.
+
+/// A pseudo-random number generator which can be created from a seed.
+pub trait SeededRng {
+    /// Creates a generator whose output is determined entirely by `seed`.
+    fn from_seed(seed: u64) -> Self;
+
+    /// Returns the next pseudo-random value.
+    fn next_u64(&mut self) -> u64;
+}
+
+/// Marsaglia's xorshift64 generator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl SeededRng for XorShift64 {
+    fn from_seed(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeroes
+        XorShift64 {
+            state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed },
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+}
+
+/// Creates an effect which seeds a new `R` with `seed` and passes it to `f`.
+///
+/// Running the effect with the same seed always gives `f` the same sequence
+/// of random values, so randomized effects can be tested deterministically.
+pub fn with_seeded_rng<A, R, F>(seed: u64, f: F) -> impl FnOnce() -> A
+    where R: SeededRng,
+          F: FnOnce(&mut R) -> A,
+{
+    move || f(&mut R::from_seed(seed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roll(rng: &mut XorShift64) -> [u64; 4] {
+        [rng.next_u64() % 6, rng.next_u64() % 6, rng.next_u64() % 6, rng.next_u64() % 6]
+    }
+
+    #[test]
+    fn with_seeded_rng_is_reproducible() {
+        assert_eq!(with_seeded_rng(42, roll)(), with_seeded_rng(42, roll)());
+    }
+
+    #[test]
+    fn with_seeded_rng_differs_by_seed() {
+        let a = with_seeded_rng(1, |rng: &mut XorShift64| rng.next_u64())();
+        let b = with_seeded_rng(2, |rng: &mut XorShift64| rng.next_u64())();
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,1261 @@
+//! Combinators for running many effects drawn from an iterator.
+
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use core::hash::Hash;
+#[cfg(feature = "alloc")]
+use core::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
+
+/// The result of an effect from one of two lists, as produced by
+/// `interleave`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Runs a sequence of effects one at a time, letting the caller look at the
+/// next result before deciding whether to take it.
+///
+/// `peek` runs the next effect and holds on to its result, and `advance`
+/// hands that result over, so an effect is never run twice.
+pub struct PeekableEffectRun<A, I> {
+    effects: I,
+    peeked: Option<A>,
+}
+
+impl<A, E, I> PeekableEffectRun<A, I>
+    where E: FnOnce() -> A,
+          I: Iterator<Item = E>,
+{
+    /// Creates a run over `effects`. Nothing is run until the first `peek`
+    /// or `advance`.
+    pub fn new<It>(effects: It) -> Self
+        where It: IntoIterator<IntoIter = I, Item = E>,
+    {
+        PeekableEffectRun {
+            effects: effects.into_iter(),
+            peeked: None,
+        }
+    }
+
+    /// Returns the result of the next effect, running it if it hasn't been
+    /// peeked at already, or `None` if there are no effects left.
+    pub fn peek(&mut self) -> Option<&A> {
+        if self.peeked.is_none() {
+            self.peeked = self.effects.next().map(|e| e());
+        }
+        self.peeked.as_ref()
+    }
+
+    /// Returns the result of the next effect and moves past it. A result
+    /// which has already been peeked at is returned without running anything.
+    pub fn advance(&mut self) -> Option<A> {
+        self.peeked.take().or_else(|| self.effects.next().map(|e| e()))
+    }
+}
+
+/// Creates an effect which drives `iter` one item at a time, running the
+/// effect produced by `f` for each item.
+///
+/// Iteration stops as soon as one of the per-item effects returns
+/// `ControlFlow::Break`, so later items are never passed to `f`.
+pub fn drive<A, I, F, E>(iter: I, mut f: F) -> impl FnOnce()
+    where I: IntoIterator<Item = A>,
+          F: FnMut(A) -> E,
+          E: FnOnce() -> ControlFlow<()>,
+{
+    move || {
+        for a in iter {
+            if f(a)().is_break() {
+                break;
+            }
+        }
+    }
+}
+
+/// Creates an effect which runs `e` exactly `n` times, discarding every
+/// result.
+pub fn replicate_ignore<E, A>(n: usize, e: E) -> impl FnOnce()
+    where E: Fn() -> A,
+{
+    move || {
+        for _ in 0..n {
+            e();
+        }
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, returning the
+/// first `Some` result.
+///
+/// Effects after the first `Some` are never run.
+pub fn first_some<A, E, I>(effects: I) -> impl FnOnce() -> Option<A>
+    where E: FnOnce() -> Option<A>,
+          I: IntoIterator<Item = E>,
+{
+    move || effects.into_iter().find_map(|e| e())
+}
+
+/// Creates an effect which runs each of the fallible `effects` in order,
+/// returning the first `Ok` result.
+///
+/// Effects after the first success are never run. If every effect fails, the
+/// errors are returned in the order they were produced.
+#[cfg(feature = "alloc")]
+pub fn first_ok<A, E, Err, I>(effects: I) -> impl FnOnce() -> Result<A, Vec<Err>>
+    where E: FnOnce() -> Result<A, Err>,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut errors = Vec::new();
+        for e in effects {
+            match e() {
+                Ok(a) => return Ok(a),
+                Err(err) => errors.push(err),
+            }
+        }
+        Err(errors)
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, running `sep`
+/// between each consecutive pair, and collects the main effects' results.
+///
+/// `sep` is not run before the first effect or after the last one.
+#[cfg(feature = "alloc")]
+pub fn intersperse<A, B, E, S, I>(effects: I, sep: S) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A,
+          S: Fn() -> B,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut results = Vec::new();
+        for e in effects {
+            if !results.is_empty() {
+                sep();
+            }
+            results.push(e());
+        }
+        results
+    }
+}
+
+/// Creates an effect which runs `effects` in order, `batch_size` at a time,
+/// running `between` at each boundary between two batches, and collects the
+/// results.
+///
+/// `between` is not run after the final batch, even if it is full.
+///
+/// # Panics
+///
+/// Panics if `batch_size` is zero.
+#[cfg(feature = "alloc")]
+pub fn sequence_chunked<A, E, B, S, I>(effects: I, batch_size: usize, between: S) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A,
+          S: Fn() -> B,
+          I: IntoIterator<Item = E>,
+{
+    assert!(batch_size != 0, "batch size must be non-zero");
+    move || {
+        let mut results = Vec::new();
+        for e in effects {
+            if !results.is_empty() && results.len() % batch_size == 0 {
+                between();
+            }
+            results.push(e());
+        }
+        results
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order and buckets their
+/// results by `key`.
+///
+/// Results within a bucket keep the order in which they were produced.
+#[cfg(feature = "std")]
+pub fn group_by<A, K, E, F, I>(effects: I, key: F) -> impl FnOnce() -> HashMap<K, Vec<A>>
+    where K: Hash + Eq,
+          F: Fn(&A) -> K,
+          E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut groups: HashMap<K, Vec<A>> = HashMap::new();
+        for e in effects {
+            let a = e();
+            groups.entry(key(&a)).or_default().push(a);
+        }
+        groups
+    }
+}
+
+/// Creates an effect which runs the value effect of each of `entries` in
+/// order, collecting the results into a map under their keys.
+///
+/// If a key appears more than once, every one of its effects still runs, and
+/// the last result wins.
+#[cfg(feature = "std")]
+pub fn collect_map<K, V, E, I>(entries: I) -> impl FnOnce() -> HashMap<K, V>
+    where K: Hash + Eq,
+          E: FnOnce() -> V,
+          I: IntoIterator<Item = (K, E)>,
+{
+    move || entries.into_iter().map(|(k, e)| (k, e())).collect()
+}
+
+/// Creates an effect which runs the whole of `effects`, in order, `cycles`
+/// times, collecting every result.
+#[cfg(feature = "alloc")]
+pub fn cycle_n<A, E>(effects: Vec<E>, cycles: usize) -> impl FnOnce() -> Vec<A>
+    where E: Fn() -> A,
+{
+    move || {
+        let mut results = Vec::with_capacity(effects.len() * cycles);
+        for _ in 0..cycles {
+            results.extend(effects.iter().map(|e| e()));
+        }
+        results
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, collecting their
+/// results, and calls `observer` with each result as it is produced.
+#[cfg(feature = "alloc")]
+pub fn sequence_tap<A, E, F, I>(effects: I, mut observer: F) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A,
+          F: FnMut(&A),
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        effects.into_iter()
+            .map(|e| {
+                let a = e();
+                observer(&a);
+                a
+            })
+            .collect()
+    }
+}
+
+/// Creates an effect which runs each of the fallible `effects` in order,
+/// collecting `Ok` values until the first `Err`.
+///
+/// Unlike a short-circuiting sequence, progress made before the failure is
+/// kept: the successes so far are returned along with the error, if any.
+/// Effects after the error are never run.
+#[cfg(feature = "alloc")]
+pub fn sequence_until_err<A, E, Eff, I>(effects: I) -> impl FnOnce() -> (Vec<A>, Option<E>)
+    where Eff: FnOnce() -> Result<A, E>,
+          I: IntoIterator<Item = Eff>,
+{
+    move || {
+        let mut results = Vec::new();
+        for e in effects {
+            match e() {
+                Ok(a) => results.push(a),
+                Err(err) => return (results, Some(err)),
+            }
+        }
+        (results, None)
+    }
+}
+
+/// Creates an effect which runs each of the fallible `effects` in order,
+/// returning all of their values, or the first error.
+///
+/// Effects after the first error are never run. The results vector is
+/// allocated up front with room for every effect, so it never reallocates.
+#[cfg(feature = "alloc")]
+pub fn sequence_result_sized<A, Err, Eff>(effects: Vec<Eff>) -> impl FnOnce() -> Result<Vec<A>, Err>
+    where Eff: FnOnce() -> Result<A, Err>,
+{
+    move || {
+        let mut results = Vec::with_capacity(effects.len());
+        for e in effects {
+            results.push(e()?);
+        }
+        Ok(results)
+    }
+}
+
+/// Creates an effect which runs every one of the fallible `effects`,
+/// returning all of their values if none failed and all of the errors
+/// otherwise.
+///
+/// Unlike a short-circuiting sequence, every effect is run, so every error is
+/// reported. Both values and errors are kept in the order they were produced.
+#[cfg(feature = "alloc")]
+pub fn validate_all<A, E, Eff, I>(effects: I) -> impl FnOnce() -> Result<Vec<A>, Vec<E>>
+    where Eff: FnOnce() -> Result<A, E>,
+          I: IntoIterator<Item = Eff>,
+{
+    move || {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for e in effects {
+            match e() {
+                Ok(a) => values.push(a),
+                Err(err) => errors.push(err),
+            }
+        }
+        if errors.is_empty() { Ok(values) } else { Err(errors) }
+    }
+}
+
+/// Creates an effect which runs `e` repeatedly until its result satisfies
+/// `pred`, returning that result.
+pub fn repeat_until<A, E, P>(e: E, pred: P) -> impl FnOnce() -> A
+    where E: Fn() -> A,
+          P: Fn(&A) -> bool,
+{
+    move || loop {
+        let a = e();
+        if pred(&a) {
+            return a;
+        }
+    }
+}
+
+/// Like `repeat_until`, but runs `e` at most `max_attempts` times.
+///
+/// Returns `Ok` with the first result satisfying `pred`, or `Err` with the
+/// last result if none did. If `max_attempts` is zero `e` never runs and the
+/// error is `None`. `pred` is checked exactly once per result.
+pub fn repeat_until_n<A, E, P>(e: E, pred: P, max_attempts: usize) -> impl FnOnce() -> Result<A, Option<A>>
+    where E: Fn() -> A,
+          P: Fn(&A) -> bool,
+{
+    move || {
+        let mut last = None;
+        for _ in 0..max_attempts {
+            let a = e();
+            if pred(&a) {
+                return Ok(a);
+            }
+            last = Some(a);
+        }
+        Err(last)
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, applying `f` to
+/// each result along with its position, and collects the mapped values.
+#[cfg(feature = "alloc")]
+pub fn sequence_map_indexed<A, B, E, F, I>(effects: I, mut f: F) -> impl FnOnce() -> Vec<B>
+    where E: FnOnce() -> A,
+          F: FnMut(usize, A) -> B,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        effects.into_iter()
+            .enumerate()
+            .map(|(i, e)| f(i, e()))
+            .collect()
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order and returns every
+/// overlapping pair of consecutive results.
+///
+/// Each effect is run exactly once; results in the middle of the sequence are
+/// cloned so they can appear in two pairs.
+#[cfg(feature = "alloc")]
+pub fn sequence_windows<A, E, I>(effects: I) -> impl FnOnce() -> Vec<(A, A)>
+    where A: Clone,
+          E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut pairs = Vec::new();
+        let mut prev: Option<A> = None;
+        for e in effects {
+            let a = e();
+            if let Some(p) = prev.take() {
+                pairs.push((p, a.clone()));
+            }
+            prev = Some(a);
+        }
+        pairs
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, folding their
+/// results together with `f` as they're produced.
+///
+/// Nothing is collected, so this avoids allocating when only an aggregate is
+/// needed. Returns `None` if there are no effects.
+pub fn reduce_effects<A, E, F, I>(effects: I, f: F) -> impl FnOnce() -> Option<A>
+    where E: FnOnce() -> A,
+          F: FnMut(A, A) -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || effects.into_iter().map(|e| e()).reduce(f)
+}
+
+/// Creates an effect which runs each of the fallible `effects` in order,
+/// folding their `Ok` values into `init` with `f`.
+///
+/// The fold stops at the first `Err`, which is returned; effects after it are
+/// never run.
+pub fn fold_results<A, B, E, F, Eff, I>(init: B, effects: I, mut f: F) -> impl FnOnce() -> Result<B, E>
+    where Eff: FnOnce() -> Result<A, E>,
+          F: FnMut(B, A) -> B,
+          I: IntoIterator<Item = Eff>,
+{
+    move || effects.into_iter().try_fold(init, |acc, e| e().map(|a| f(acc, a)))
+}
+
+/// Creates an effect which runs `e` `total` times, keeping only every
+/// `every`th result (the `every`th, `2 * every`th, and so on).
+///
+/// Every run still happens; only the returned results are thinned out.
+///
+/// # Panics
+///
+/// Panics if `every` is zero.
+#[cfg(feature = "alloc")]
+pub fn sample<A, E>(e: E, total: usize, every: usize) -> impl FnOnce() -> Vec<A>
+    where E: Fn() -> A,
+{
+    assert!(every != 0, "sample interval must be non-zero");
+    move || {
+        let mut kept = Vec::with_capacity(total / every);
+        for run in 1..=total {
+            let a = e();
+            if run % every == 0 {
+                kept.push(a);
+            }
+        }
+        kept
+    }
+}
+
+/// Creates an effect which runs every effect in `a`, then every effect in
+/// `b`, and zips their results, padding the shorter side with `None`.
+///
+/// All of `a` runs before any of `b`; the two lists are not interleaved.
+#[cfg(feature = "alloc")]
+pub fn zip_sequences<A, B, Ea, Eb>(a: Vec<Ea>, b: Vec<Eb>) -> impl FnOnce() -> Vec<(Option<A>, Option<B>)>
+    where Ea: FnOnce() -> A,
+          Eb: FnOnce() -> B,
+{
+    move || {
+        let a: Vec<A> = a.into_iter().map(|e| e()).collect();
+        let b: Vec<B> = b.into_iter().map(|e| e()).collect();
+        let len = a.len().max(b.len());
+        let (mut a, mut b) = (a.into_iter(), b.into_iter());
+        (0..len).map(|_| (a.next(), b.next())).collect()
+    }
+}
+
+/// Creates an effect which runs effects from `a` and `b` alternately, starting
+/// with `a`, and collects their results in the order they ran.
+///
+/// Once the shorter list runs out, the rest of the longer one runs in order.
+#[cfg(feature = "alloc")]
+pub fn interleave<A, B, Ea, Eb>(a: Vec<Ea>, b: Vec<Eb>) -> impl FnOnce() -> Vec<Either<A, B>>
+    where Ea: FnOnce() -> A,
+          Eb: FnOnce() -> B,
+{
+    move || {
+        let mut results = Vec::with_capacity(a.len() + b.len());
+        let (mut a, mut b) = (a.into_iter(), b.into_iter());
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return results,
+                (ea, eb) => {
+                    results.extend(ea.map(|e| Either::Left(e())));
+                    results.extend(eb.map(|e| Either::Right(e())));
+                }
+            }
+        }
+    }
+}
+
+/// Creates an effect which runs every effect in `a`, then every effect in
+/// `b`, and merges their results into one sorted vector.
+///
+/// Each list's results are assumed to already be sorted; they are merged,
+/// not sorted, so if either isn't, neither is the output. Where results
+/// compare equal, those from `a` come first.
+#[cfg(feature = "alloc")]
+pub fn merge_sorted<A, Ea, Eb>(a: Vec<Ea>, b: Vec<Eb>) -> impl FnOnce() -> Vec<A>
+    where A: Ord,
+          Ea: FnOnce() -> A,
+          Eb: FnOnce() -> A,
+{
+    move || {
+        let a: Vec<A> = a.into_iter().map(|e| e()).collect();
+        let b: Vec<A> = b.into_iter().map(|e| e()).collect();
+        let mut merged = Vec::with_capacity(a.len() + b.len());
+        let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+        loop {
+            let take_a = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) => x <= y,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return merged,
+            };
+            merged.extend(if take_a { a.next() } else { b.next() });
+        }
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, dropping any
+/// result equal to the last one kept.
+///
+/// Every effect is run; only consecutive duplicate results are removed.
+#[cfg(feature = "alloc")]
+pub fn sequence_dedup<A, E, I>(effects: I) -> impl FnOnce() -> Vec<A>
+    where A: PartialEq,
+          E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut results: Vec<A> = Vec::new();
+        for e in effects {
+            let a = e();
+            if results.last() != Some(&a) {
+                results.push(a);
+            }
+        }
+        results
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, splitting their
+/// results into those matching `pred` and those that don't.
+///
+/// Order is preserved within each of the two vectors.
+#[cfg(feature = "alloc")]
+pub fn sequence_partition<A, E, P, I>(effects: I, pred: P) -> impl FnOnce() -> (Vec<A>, Vec<A>)
+    where P: Fn(&A) -> bool,
+          E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || effects.into_iter().map(|e| e()).partition(|a| pred(a))
+}
+
+/// Creates an effect which runs every one of the fallible `effects`,
+/// collecting the `Ok` values and passing each error to `on_err`.
+///
+/// Nothing short-circuits: every effect runs, and errors reach `on_err` in
+/// the order they were produced.
+#[cfg(feature = "alloc")]
+pub fn sequence_lenient<A, E, Err, S, I>(effects: I, mut on_err: S) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> Result<A, Err>,
+          S: FnMut(Err),
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let mut values = Vec::new();
+        for e in effects {
+            match e() {
+                Ok(a) => values.push(a),
+                Err(err) => on_err(err),
+            }
+        }
+        values
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order until one's result
+/// satisfies `crossed`, returning the results so far and the index at which
+/// it stopped.
+///
+/// The crossing result is the last one collected, and its index is returned.
+/// Effects after it are never run. If no result crosses the threshold, every
+/// effect is run and the index is `effects.len()`.
+#[cfg(feature = "alloc")]
+pub fn run_until_threshold<A, E, P>(effects: Vec<E>, crossed: P) -> impl FnOnce() -> (Vec<A>, usize)
+    where P: Fn(&A) -> bool,
+          E: FnOnce() -> A,
+{
+    move || {
+        let len = effects.len();
+        let mut results = Vec::with_capacity(len);
+        for (i, e) in effects.into_iter().enumerate() {
+            let a = e();
+            let stop = crossed(&a);
+            results.push(a);
+            if stop {
+                return (results, i);
+            }
+        }
+        (results, len)
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, collecting their
+/// results and calling `std::thread::yield_now` after every `yield_every`
+/// effects.
+///
+/// Yielding doesn't change the results; it only gives other threads a chance
+/// to run during a long sequence.
+///
+/// # Panics
+///
+/// Panics if `yield_every` is zero.
+#[cfg(feature = "std")]
+pub fn sequence_yielding<A, E, I>(effects: I, yield_every: usize) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    assert!(yield_every != 0, "yield interval must be non-zero");
+    move || {
+        let mut results = Vec::new();
+        for e in effects {
+            results.push(e());
+            if results.len() % yield_every == 0 {
+                std::thread::yield_now();
+            }
+        }
+        results
+    }
+}
+
+/// Creates an effect which runs every one of the fallible `effects`,
+/// sending each `Ok` value down `ok_tx` and each error down `err_tx`.
+///
+/// Nothing short-circuits: every effect runs, and each channel receives its
+/// values in the order they were produced. A disconnected receiver is
+/// ignored.
+#[cfg(feature = "std")]
+pub fn sequence_to_channels<A, E, Err, I>(effects: I, ok_tx: Sender<A>, err_tx: Sender<Err>) -> impl FnOnce()
+    where E: FnOnce() -> Result<A, Err>,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        for e in effects {
+            // The receivers may have hung up; the effects still all run
+            match e() {
+                Ok(a) => drop(ok_tx.send(a)),
+                Err(err) => drop(err_tx.send(err)),
+            }
+        }
+    }
+}
+
+/// Creates an effect which runs every effect in `xs`, then every effect in
+/// `ys`, and returns every pairing of their results, ordered by `xs` first.
+///
+/// Each input effect is run exactly once, not once per pair; the results are
+/// cloned into the pairs that use them.
+#[cfg(feature = "alloc")]
+pub fn cartesian<A, B, Ea, Eb, Ia, Ib>(xs: Ia, ys: Ib) -> impl FnOnce() -> Vec<(A, B)>
+    where A: Clone,
+          B: Clone,
+          Ea: FnOnce() -> A,
+          Eb: FnOnce() -> B,
+          Ia: IntoIterator<Item = Ea>,
+          Ib: IntoIterator<Item = Eb>,
+{
+    move || {
+        let xs: Vec<A> = xs.into_iter().map(|e| e()).collect();
+        let ys: Vec<B> = ys.into_iter().map(|e| e()).collect();
+        xs.iter()
+            .flat_map(|a| ys.iter().map(move |b| (a.clone(), b.clone())))
+            .collect()
+    }
+}
+
+/// The effects left over when a `sequence_resumable` run pauses.
+///
+/// Calling it runs the rest of the effects in order and collects their
+/// results.
+#[cfg(feature = "alloc")]
+pub struct Resume<A, E> {
+    rest: alloc::vec::IntoIter<E>,
+    _result: PhantomData<fn() -> A>,
+}
+
+#[cfg(feature = "alloc")]
+impl<A, E> Resume<A, E> {
+    /// Returns the number of effects which haven't been run yet.
+    pub fn remaining(&self) -> usize {
+        self.rest.len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A, E> FnOnce<()> for Resume<A, E>
+    where E: FnOnce() -> A,
+{
+    type Output = Vec<A>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        self.rest.map(|e| e()).collect()
+    }
+}
+
+/// Creates an effect which runs the first `pause_after` of `effects` in
+/// order, then pauses, returning their results along with a `Resume` which
+/// runs the rest.
+///
+/// If every effect runs before the pause, there is nothing to resume and
+/// `None` is returned in its place.
+#[cfg(feature = "alloc")]
+pub fn sequence_resumable<A, E>(effects: Vec<E>, pause_after: usize) -> impl FnOnce() -> (Vec<A>, Option<Resume<A, E>>)
+    where E: FnOnce() -> A,
+{
+    move || {
+        let mut rest = effects.into_iter();
+        let results = rest.by_ref().take(pause_after).map(|e| e()).collect();
+        let resume = if rest.len() == 0 {
+            None
+        } else {
+            Some(Resume {
+                rest,
+                _result: PhantomData,
+            })
+        };
+        (results, resume)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+    use testing::counted;
+
+    #[test]
+    fn drive_stops_at_break() {
+        let seen: [Cell<usize>; 5] = Default::default();
+        let pseen = &seen;
+        drive(0..5, |i: usize| move || {
+            pseen[i].set(pseen[i].get() + 1);
+            if i == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })();
+        assert_eq!(seen.map(Cell::into_inner), [1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn drive_runs_every_item_without_break() {
+        let count = Cell::new(0);
+        let pcount = &count;
+        drive(0..4, |_| move || {
+            pcount.set(pcount.get() + 1);
+            ControlFlow::Continue(())
+        })();
+        assert_eq!(count.get(), 4);
+    }
+
+    #[test]
+    fn replicate_ignore_runs_n_times() {
+        let count = Cell::new(0);
+        replicate_ignore(3, || count.set(count.get() + 1))();
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn replicate_ignore_zero_never_runs() {
+        let count = Cell::new(0);
+        replicate_ignore(0, || count.set(count.get() + 1))();
+        assert_eq!(count.get(), 0);
+    }
+
+    #[test]
+    fn first_some_short_circuits() {
+        let runs = Cell::new(0);
+        let effect = |v: Option<i32>| counted(&runs, v);
+        let result = first_some([effect(None), effect(Some(5)), effect(Some(9))])();
+        assert_eq!(result, Some(5));
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn first_ok_runs_until_success() {
+        let runs = Cell::new(0);
+        let effect = |v: Result<i32, &'static str>| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                v
+            }
+        };
+        let result = first_ok(vec![effect(Err("a")), effect(Err("b")), effect(Ok(3))])();
+        assert_eq!(result, Ok(3));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn first_ok_collects_every_error() {
+        let result = first_ok(vec![|| Err::<(), _>("a"), || Err("b")])();
+        assert_eq!(result, Err(vec!["a", "b"]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn intersperse_runs_sep_between_effects() {
+        use core::cell::RefCell;
+
+        let log = RefCell::new(Vec::new());
+        let effect = |v: i32| {
+            let log = &log;
+            move || {
+                log.borrow_mut().push(v);
+                v
+            }
+        };
+        let results = intersperse(vec![effect(1), effect(2), effect(3)], || log.borrow_mut().push(0))();
+        assert_eq!(results, vec![1, 2, 3]);
+        assert_eq!(log.into_inner(), vec![1, 0, 2, 0, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_chunked_runs_between_batches() {
+        let betweens = Cell::new(0);
+        let results = sequence_chunked((0..5).map(|i| move || i), 2, || betweens.set(betweens.get() + 1))();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert_eq!(betweens.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_chunked_skips_trailing_between() {
+        let betweens = Cell::new(0);
+        sequence_chunked((0..4).map(|i| move || i), 2, || betweens.set(betweens.get() + 1))();
+        assert_eq!(betweens.get(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn group_by_buckets_in_order() {
+        let groups = group_by(vec![|| 1, || 2, || 3], |a: &i32| a % 2 == 0)();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&false], vec![1, 3]);
+        assert_eq!(groups[&true], vec![2]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn cycle_n_repeats_in_order() {
+        let results = cycle_n(vec![|| 'a', || 'b'], 3)();
+        assert_eq!(results, vec!['a', 'b', 'a', 'b', 'a', 'b']);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_tap_observes_each_result() {
+        let mut seen = Vec::new();
+        let results = sequence_tap((1..4).map(|i| move || i), |a: &i32| seen.push(*a))();
+        assert_eq!(results, vec![1, 2, 3]);
+        assert_eq!(seen, vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sequence_tap_runs_effects_in_order() {
+        let tracker = ::testing::SeqTracker::new();
+        let effects = (0..4).map(|i| tracker.track(i, move || i));
+        let results = sequence_tap(effects, |_| {})();
+        assert_eq!(results, vec![0, 1, 2, 3]);
+        tracker.assert_order(&[0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_until_err_keeps_progress() {
+        let runs = Cell::new(0);
+        let effect = |v: Result<i32, &'static str>| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                v
+            }
+        };
+        let effects = vec![effect(Ok(1)), effect(Ok(2)), effect(Err("e")), effect(Ok(3))];
+        assert_eq!(sequence_until_err(effects)(), (vec![1, 2], Some("e")));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn validate_all_collects_every_error() {
+        let runs = Cell::new(0);
+        let effect = |v: Result<i32, &'static str>| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                v
+            }
+        };
+        let effects = vec![effect(Err("a")), effect(Ok(1)), effect(Err("b"))];
+        assert_eq!(validate_all(effects)(), Err(vec!["a", "b"]));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn validate_all_returns_values_when_clean() {
+        let effects = (1..4).map(|i| move || Ok::<_, ()>(i));
+        assert_eq!(validate_all(effects)(), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn repeat_until_stops_when_satisfied() {
+        let runs = Cell::new(0);
+        let result = repeat_until(|| {
+            runs.set(runs.get() + 1);
+            runs.get() * 10
+        }, |a| *a >= 30)();
+        assert_eq!(result, 30);
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn repeat_until_n_caps_attempts() {
+        let runs = Cell::new(0);
+        let poll = || {
+            runs.set(runs.get() + 1);
+            runs.get()
+        };
+        assert_eq!(repeat_until_n(poll, |a| *a == 3, 5)(), Ok(3));
+        runs.set(0);
+        assert_eq!(repeat_until_n(poll, |a| *a == 3, 2)(), Err(Some(2)));
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn repeat_until_n_zero_cap_never_runs() {
+        let runs = Cell::new(0);
+        let result = repeat_until_n(|| runs.set(runs.get() + 1), |_| true, 0)();
+        assert_eq!(result, Err(None));
+        assert_eq!(runs.get(), 0);
+    }
+
+    #[test]
+    fn repeat_until_n_checks_each_result_once() {
+        let checks = Cell::new(0);
+        let pred = |a: &i32| {
+            checks.set(checks.get() + 1);
+            *a > 0
+        };
+        assert_eq!(repeat_until_n(|| 1, pred, 3)(), Ok(1));
+        assert_eq!(checks.get(), 1);
+        checks.set(0);
+        assert_eq!(repeat_until_n(|| 0, pred, 3)(), Err(Some(0)));
+        assert_eq!(checks.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_map_indexed_passes_positions() {
+        let results = sequence_map_indexed(vec![|| 'a', || 'b', || 'c'], |i, c| (i, c))();
+        assert_eq!(results, vec![(0, 'a'), (1, 'b'), (2, 'c')]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_windows_pairs_neighbours() {
+        let runs = Cell::new(0);
+        let effects = (1..4).map(|i| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                i
+            }
+        });
+        assert_eq!(sequence_windows(effects)(), vec![(1, 2), (2, 3)]);
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn reduce_effects_folds_results() {
+        assert_eq!(reduce_effects((1..4).map(|i| move || i), |a, b| a + b)(), Some(6));
+    }
+
+    #[test]
+    fn reduce_effects_empty_is_none() {
+        assert_eq!(reduce_effects(core::iter::empty::<fn() -> i32>(), |a, b| a + b)(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sample_keeps_every_nth() {
+        let runs = Cell::new(0);
+        let results = sample(|| {
+            runs.set(runs.get() + 1);
+            runs.get()
+        }, 6, 2)();
+        assert_eq!(results, vec![2, 4, 6]);
+        assert_eq!(runs.get(), 6);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn zip_sequences_pads_shorter_side() {
+        use core::cell::RefCell;
+
+        let order = RefCell::new(Vec::new());
+        let record = |tag: char| {
+            let order = &order;
+            move || {
+                order.borrow_mut().push(tag);
+                tag
+            }
+        };
+        let results = zip_sequences(vec![record('a'), record('b')], vec![record('x')])();
+        assert_eq!(results, vec![(Some('a'), Some('x')), (Some('b'), None)]);
+        assert_eq!(order.into_inner(), vec!['a', 'b', 'x']);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_dedup_drops_consecutive_duplicates() {
+        let runs = Cell::new(0);
+        let effects = vec![1, 1, 2, 2, 1].into_iter().map(|i| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                i
+            }
+        });
+        assert_eq!(sequence_dedup(effects)(), vec![1, 2, 1]);
+        assert_eq!(runs.get(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_partition_splits_by_predicate() {
+        let effects = (1..5).map(|i| move || i);
+        assert_eq!(sequence_partition(effects, |a| a % 2 == 0)(), (vec![2, 4], vec![1, 3]));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_lenient_routes_errors_to_sink() {
+        let mut errors = Vec::new();
+        let effects = vec![|| Ok(1), || Err("a"), || Ok(2), || Err("b")];
+        let values = sequence_lenient(effects, |e| errors.push(e))();
+        assert_eq!(values, vec![1, 2]);
+        assert_eq!(errors, vec!["a", "b"]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn run_until_threshold_stops_at_crossing() {
+        let runs = Cell::new(0);
+        let effects = (1..6).map(|i| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                i * 10
+            }
+        }).collect();
+        assert_eq!(run_until_threshold(effects, |a| *a >= 30)(), (vec![10, 20, 30], 2));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn run_until_threshold_runs_all_without_crossing() {
+        let effects = vec![|| 1, || 2];
+        assert_eq!(run_until_threshold(effects, |a| *a > 5)(), (vec![1, 2], 2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sequence_yielding_runs_every_effect_in_order() {
+        use core::cell::RefCell;
+
+        let order = RefCell::new(Vec::new());
+        let effects = (0..7).map(|i| {
+            let order = &order;
+            move || {
+                order.borrow_mut().push(i);
+                i * 2
+            }
+        });
+        assert_eq!(sequence_yielding(effects, 3)(), vec![0, 2, 4, 6, 8, 10, 12]);
+        assert_eq!(order.into_inner(), vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sequence_to_channels_routes_oks_and_errors() {
+        use std::sync::mpsc;
+
+        let (ok_tx, ok_rx) = mpsc::channel();
+        let (err_tx, err_rx) = mpsc::channel();
+        let effects = vec![|| Ok(1), || Err("a"), || Ok(2), || Err("b")];
+        sequence_to_channels(effects, ok_tx, err_tx)();
+        assert_eq!(ok_rx.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(err_rx.iter().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn cartesian_runs_each_input_once() {
+        let runs = Cell::new(0);
+        let effect = |v: i32| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                v
+            }
+        };
+        let pairs = cartesian(vec![effect(1), effect(2)], vec![effect(3), effect(4)])();
+        assert_eq!(pairs, vec![(1, 3), (1, 4), (2, 3), (2, 4)]);
+        assert_eq!(runs.get(), 4);
+    }
+
+    #[test]
+    fn fold_results_stops_at_first_err() {
+        let runs = Cell::new(0);
+        let effect = |v: Result<i32, &'static str>| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                v
+            }
+        };
+        let effects = [effect(Ok(1)), effect(Ok(2)), effect(Err("bad")), effect(Ok(4))];
+        assert_eq!(fold_results(0, effects, |acc, a| acc + a)(), Err("bad"));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn fold_results_folds_every_ok() {
+        let effects = (1..5).map(|i| move || Ok::<_, ()>(i));
+        assert_eq!(fold_results(0, effects, |acc, a| acc + a)(), Ok(10));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_resumable_pauses_and_resumes_in_order() {
+        use core::cell::RefCell;
+
+        let order = RefCell::new(Vec::new());
+        let effects = (0..4).map(|i| {
+            let order = &order;
+            move || {
+                order.borrow_mut().push(i);
+                i * 10
+            }
+        }).collect();
+        let (first, resume) = sequence_resumable(effects, 2)();
+        assert_eq!(first, vec![0, 10]);
+        assert_eq!(*order.borrow(), vec![0, 1]);
+        let resume = resume.unwrap();
+        assert_eq!(resume.remaining(), 2);
+        assert_eq!(resume(), vec![20, 30]);
+        assert_eq!(order.into_inner(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_resumable_has_nothing_to_resume_when_done() {
+        let (results, resume) = sequence_resumable(vec![|| 1, || 2], 5)();
+        assert_eq!(results, vec![1, 2]);
+        assert!(resume.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn collect_map_runs_entries_in_order() {
+        use core::cell::RefCell;
+
+        let order = RefCell::new(Vec::new());
+        let entry = |k: &'static str, v: i32| {
+            let order = &order;
+            (k, move || {
+                order.borrow_mut().push(k);
+                v
+            })
+        };
+        let map = collect_map(vec![entry("a", 1), entry("b", 2), entry("c", 3)])();
+        assert_eq!(map.len(), 3);
+        assert_eq!((map["a"], map["b"], map["c"]), (1, 2, 3));
+        assert_eq!(order.into_inner(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn collect_map_keeps_last_duplicate() {
+        let map = collect_map((1..3).map(|v| ("k", move || v)))();
+        assert_eq!(map["k"], 2);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn interleave_alternates_and_appends_tail() {
+        use self::Either::{Left, Right};
+
+        let results = interleave((1..4).map(|i| move || i).collect(), vec![|| 'x'])();
+        assert_eq!(results, vec![Left(1), Right('x'), Left(2), Left(3)]);
+
+        let results = interleave(vec![|| 1], (0..3).map(|i| move || i * 10).collect())();
+        assert_eq!(results, vec![Left(1), Right(0), Right(10), Right(20)]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn merge_sorted_merges_sorted_results() {
+        let a = [1, 4, 4, 9].iter().map(|&i| move || i).collect();
+        let b = [2, 4, 10].iter().map(|&i| move || i).collect();
+        assert_eq!(merge_sorted(a, b)(), vec![1, 2, 4, 4, 4, 9, 10]);
+    }
+
+    #[test]
+    fn peekable_effect_run_reuses_peeked_result() {
+        let runs = Cell::new(0);
+        let effects = (1..3).map(|i| {
+            let runs = &runs;
+            move || {
+                runs.set(runs.get() + 1);
+                i
+            }
+        });
+        let mut run = PeekableEffectRun::new(effects);
+        assert_eq!(runs.get(), 0);
+        assert_eq!(run.peek(), Some(&1));
+        assert_eq!(run.peek(), Some(&1));
+        assert_eq!(runs.get(), 1);
+        assert_eq!(run.advance(), Some(1));
+        assert_eq!(runs.get(), 1);
+        assert_eq!(run.advance(), Some(2));
+        assert_eq!(run.peek(), None);
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn sequence_result_sized_matches_collect() {
+        let runs = Cell::new(0);
+        let effects = |results: &[Result<i32, &'static str>]| -> Vec<_> {
+            results.iter().map(|&r| {
+                let runs = &runs;
+                move || {
+                    runs.set(runs.get() + 1);
+                    r
+                }
+            }).collect()
+        };
+
+        let values = sequence_result_sized(effects(&[Ok(3), Ok(1), Ok(2)]))().unwrap();
+        let collected: Result<Vec<_>, &str> = effects(&[Ok(3), Ok(1), Ok(2)]).into_iter().map(|e| e()).collect();
+        assert_eq!(Ok(values.clone()), collected);
+        assert_eq!(values, vec![3, 1, 2]);
+        assert_eq!(values.capacity(), 3);
+
+        runs.set(0);
+        let sized = sequence_result_sized(effects(&[Ok(1), Err("a"), Err("b")]))();
+        assert_eq!(runs.get(), 2);
+        runs.set(0);
+        let collected: Result<Vec<_>, _> = effects(&[Ok(1), Err("a"), Err("b")]).into_iter().map(|e| e()).collect();
+        assert_eq!(runs.get(), 2);
+        assert_eq!(sized, collected);
+        assert_eq!(sized, Err("a"));
+    }
+}
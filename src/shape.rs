@@ -0,0 +1,76 @@
+//! Compile-time introspection of composed effects.
+//!
+//! This relies on specialization, so it is only available with the
+//! `unstable` feature.
+
+use BoundEffect;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+
+/// Reports how many stages a composed effect is made of.
+///
+/// Any effect which isn't a `BoundEffect` is a single stage, and every `bind`
+/// adds one stage for its continuation, so `e.bind(f).bind(g).bind(h)` has
+/// four stages. This is an aid for tests and documentation; it has no effect
+/// on how an effect runs.
+pub trait EffectShape {
+    /// The number of stages in the effect.
+    const STAGES: usize;
+
+    /// Describes the shape of the effect at runtime, e.g.
+    /// `"depth 3: a single effect followed by 2 bound stages"`.
+    #[cfg(feature = "alloc")]
+    fn describe(&self) -> String {
+        match Self::STAGES {
+            1 => String::from("depth 1: a single effect"),
+            n => alloc::format!("depth {}: a single effect followed by {} bound stages", n, n - 1),
+        }
+    }
+}
+
+impl<T> EffectShape for T {
+    default const STAGES: usize = 1;
+}
+
+impl<Ea, F> EffectShape for BoundEffect<Ea, F>
+    where Ea: EffectShape,
+{
+    const STAGES: usize = Ea::STAGES + 1;
+}
+
+/// Returns the number of stages in `effect`, as reported by `EffectShape`.
+pub fn stages<E>(_effect: &E) -> usize {
+    E::STAGES
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use EffectMonad;
+
+    #[test]
+    fn single_effect_is_one_stage() {
+        assert_eq!(stages(&|| 1), 1);
+    }
+
+    #[test]
+    fn three_binds_are_four_stages() {
+        let chain = (|| 1)
+            .bind(|a| move || a + 1)
+            .bind(|a| move || a * 2)
+            .bind(|a| move || a - 1);
+        assert_eq!(stages(&chain), 4);
+        assert_eq!(chain(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn describe_reports_depth() {
+        let chain = (|| 1)
+            .bind(|a| move || a + 1)
+            .bind(|a| move || a * 2)
+            .bind(|a| move || a - 1);
+        assert_eq!(chain.describe(), "depth 4: a single effect followed by 3 bound stages");
+        assert_eq!((|| 1).describe(), "depth 1: a single effect");
+    }
+}
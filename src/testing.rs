@@ -0,0 +1,106 @@
+//! Utilities for testing the order in which effects run.
+//!
+//! This is available to the crate's own tests, and to other crates with the
+//! `testing` feature.
+
+#[cfg(test)]
+use core::cell::Cell;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Records the order in which tracked effects run, so tests can assert that
+/// combinators evaluate effects strictly in their documented order.
+///
+/// Every run is given a tick from a shared counter and its label is appended
+/// to a log, so a tracker can be shared between threads.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct SeqTracker {
+    ticks: AtomicUsize,
+    log: Mutex<Vec<usize>>,
+}
+
+#[cfg(feature = "std")]
+impl SeqTracker {
+    /// Creates a tracker which hasn't recorded anything yet.
+    pub fn new() -> Self {
+        SeqTracker::default()
+    }
+
+    /// Records that the step labelled `label` ran, returning its tick: the
+    /// number of steps recorded before it.
+    pub fn record(&self, label: usize) -> usize {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.push(label);
+        self.ticks.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Wraps `e` so that running it records `label` just before `e` runs.
+    pub fn track<'a, A, E>(&'a self, label: usize, e: E) -> impl FnOnce() -> A + 'a
+        where E: FnOnce() -> A + 'a,
+    {
+        move || {
+            self.record(label);
+            e()
+        }
+    }
+
+    /// Returns the number of steps recorded so far.
+    pub fn len(&self) -> usize {
+        self.ticks.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if no steps have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the labels recorded so far, in the order they ran.
+    pub fn order(&self) -> Vec<usize> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Panics unless exactly the steps in `expected` ran, in that order.
+    pub fn assert_order(&self, expected: &[usize]) {
+        let order = self.order();
+        assert_eq!(order, expected, "effects ran out of order");
+    }
+}
+
+/// Wraps `v` in an effect which bumps `runs` each time it's run, so tests
+/// can check how many effects a combinator actually ran.
+#[cfg(test)]
+pub(crate) fn counted<'a, T: 'a>(runs: &'a Cell<usize>, v: T) -> impl FnOnce() -> T + 'a {
+    move || {
+        runs.set(runs.get() + 1);
+        v
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seq_tracker_records_ticks_and_order() {
+        let tracker = SeqTracker::new();
+        assert!(tracker.is_empty());
+        assert_eq!(tracker.record(7), 0);
+        assert_eq!(tracker.track(3, || 1)(), 1);
+        assert_eq!(tracker.len(), 2);
+        tracker.assert_order(&[7, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "effects ran out of order")]
+    fn seq_tracker_rejects_wrong_order() {
+        let tracker = SeqTracker::new();
+        tracker.record(2);
+        tracker.record(1);
+        tracker.assert_order(&[1, 2]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Combinators which run effects on other threads.
+
+use core::fmt;
+use core::time::Duration;
+use std::error::Error;
+use std::string::{String, ToString};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Instant;
+use std::vec::Vec;
+
+/// Error returned when an effect doesn't finish within its time limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeoutError;
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("effect timed out")
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Error returned when an effect doesn't finish by its deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("effect missed its deadline")
+    }
+}
+
+impl Error for DeadlineExceeded {}
+
+/// Configuration for the threads spawned by the combinators in this module.
+///
+/// Threads are anonymous and use the default stack size unless configured
+/// otherwise, e.g. `SpawnConfig::new().name("worker")`.
+#[derive(Clone, Debug, Default)]
+pub struct SpawnConfig {
+    name: Option<String>,
+    stack_size: Option<usize>,
+}
+
+impl SpawnConfig {
+    /// Creates a configuration for anonymous threads with the default stack
+    /// size.
+    pub fn new() -> Self {
+        SpawnConfig::default()
+    }
+
+    /// Names spawned threads `name`, as reported by `thread::current().name()`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Gives spawned threads a stack of `size` bytes.
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Spawns `f` on a new thread configured by `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system fails to create the thread, as
+    /// `thread::spawn` does.
+    pub fn spawn<A, F>(&self, f: F) -> thread::JoinHandle<A>
+        where F: FnOnce() -> A + Send + 'static,
+              A: Send + 'static,
+    {
+        let mut builder = thread::Builder::new();
+        if let Some(ref name) = self.name {
+            builder = builder.name(name.clone());
+        }
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        builder.spawn(f).expect("failed to spawn thread")
+    }
+}
+
+/// Handle to an effect running in the background, returned by
+/// `EffectMonad::spawn`.
+pub struct EffectHandle<A>(pub(crate) thread::JoinHandle<A>);
+
+impl<A> EffectHandle<A> {
+    /// Waits for the effect to finish, returning its result, or `Err` with
+    /// the panic payload if it panicked.
+    pub fn join(self) -> thread::Result<A> {
+        self.0.join()
+    }
+
+    /// Returns `true` if the effect has finished running.
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+/// A sink which sends each value it's given down a channel, ignoring a
+/// disconnected receiver. Used by `EffectMonad::tee_sender`.
+pub struct SenderSink<A>(pub mpsc::Sender<A>);
+
+impl<A> FnOnce<(A,)> for SenderSink<A> {
+    type Output = ();
+    extern "rust-call" fn call_once(self, (a,): (A,)) -> Self::Output {
+        let _ = self.0.send(a);
+    }
+}
+
+/// Effect returned by `EffectMonad::until_deadline`.
+pub struct UntilDeadlineEffect<Ea> {
+    pub(crate) e: Ea,
+    pub(crate) deadline: Instant,
+}
+
+impl<A, Ea> FnOnce<()> for UntilDeadlineEffect<Ea>
+    where Ea: FnOnce() -> A + Send + 'static,
+          A: Send + 'static,
+{
+    type Output = Result<A, DeadlineExceeded>;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(DeadlineExceeded);
+        }
+        timeout(self.e, self.deadline - now)().map_err(|_| DeadlineExceeded)
+    }
+}
+
+/// Creates an effect which runs `e` on a new thread, giving up if it doesn't
+/// finish within `limit`.
+///
+/// There is no way to cancel a thread, so an effect which times out is
+/// abandoned rather than stopped: its thread keeps running in the background
+/// and its result is dropped when it finishes. Because it may outlive the
+/// caller, the effect must be `'static`.
+pub fn timeout<A, E>(e: E, limit: Duration) -> impl FnOnce() -> Result<A, TimeoutError>
+    where E: FnOnce() -> A + Send + 'static,
+          A: Send + 'static,
+{
+    timeout_with(SpawnConfig::new(), e, limit)
+}
+
+/// Like `timeout`, but spawns the effect's thread as configured by `config`.
+pub fn timeout_with<A, E>(config: SpawnConfig, e: E, limit: Duration) -> impl FnOnce() -> Result<A, TimeoutError>
+    where E: FnOnce() -> A + Send + 'static,
+          A: Send + 'static,
+{
+    move || {
+        let (tx, rx) = mpsc::channel();
+        config.spawn(move || {
+            // The receiver is gone if we've already timed out
+            let _ = tx.send(e());
+        });
+        rx.recv_timeout(limit).map_err(|_| TimeoutError)
+    }
+}
+
+/// Spawns a thread which runs each of `effects` in order, sending each result
+/// down a channel as soon as it's produced, and returns the receiving end.
+///
+/// Unlike the other combinators in this module, this starts running the
+/// effects immediately rather than returning an effect. The channel closes
+/// once every effect has run, or early if one of them panics. If the
+/// receiver is dropped, the remaining effects still run.
+pub fn sequence_to_receiver<A, E>(effects: Vec<E>) -> mpsc::Receiver<A>
+    where E: FnOnce() -> A + Send + 'static,
+          A: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    SpawnConfig::new().spawn(move || {
+        for e in effects {
+            // The receiver may have hung up; the effects still all run
+            let _ = tx.send(e());
+        }
+    });
+    rx
+}
+
+/// Creates an effect which runs `ea` and `eb` concurrently on new threads,
+/// returning whichever `Ok` arrives first.
+///
+/// If both fail, their errors are returned as `(ea's error, eb's error)`.
+/// The losing effect isn't cancelled: its thread keeps running and its
+/// result is dropped when it finishes.
+///
+/// # Panics
+///
+/// Panics if an effect panics before the race has been decided.
+pub fn race_ok<A, E, Ea, Eb>(ea: Ea, eb: Eb) -> impl FnOnce() -> Result<A, (E, E)>
+    where Ea: FnOnce() -> Result<A, E> + Send + 'static,
+          Eb: FnOnce() -> Result<A, E> + Send + 'static,
+          A: Send + 'static,
+          E: Send + 'static,
+{
+    race_ok_with(SpawnConfig::new(), ea, eb)
+}
+
+/// Like `race_ok`, but spawns both effects' threads as configured by
+/// `config`.
+pub fn race_ok_with<A, E, Ea, Eb>(config: SpawnConfig, ea: Ea, eb: Eb) -> impl FnOnce() -> Result<A, (E, E)>
+    where Ea: FnOnce() -> Result<A, E> + Send + 'static,
+          Eb: FnOnce() -> Result<A, E> + Send + 'static,
+          A: Send + 'static,
+          E: Send + 'static,
+{
+    move || {
+        let (tx, rx) = mpsc::channel();
+        let tx_b = tx.clone();
+        config.spawn(move || {
+            let _ = tx.send((true, ea()));
+        });
+        config.spawn(move || {
+            let _ = tx_b.send((false, eb()));
+        });
+
+        let mut first_err = None;
+        for (is_a, result) in rx.iter() {
+            match (result, first_err.take()) {
+                (Ok(a), _) => return Ok(a),
+                (Err(e), None) => first_err = Some((is_a, e)),
+                (Err(e), Some((true, ea_err))) => return Err((ea_err, e)),
+                (Err(e), Some((false, eb_err))) => return Err((e, eb_err)),
+            }
+        }
+        // A sender only hangs up without sending if its effect panicked
+        panic!("racing effect panicked")
+    }
+}
+
+/// Creates an effect which runs `effects` across at most `max_threads` scoped
+/// threads, collecting their results in input order.
+///
+/// Each thread takes the next effect that hasn't been started yet, so a slow
+/// effect doesn't hold up the rest of the list. The effect returns once every
+/// effect has finished.
+///
+/// # Panics
+///
+/// Panics if `max_threads` is zero, or when run if any of the effects panics.
+pub fn par_sequence_bounded<A, E>(effects: Vec<E>, max_threads: usize) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A + Send,
+          A: Send,
+{
+    assert!(max_threads != 0, "max_threads must be non-zero");
+    move || {
+        let len = effects.len();
+        let queue = Mutex::new(effects.into_iter().enumerate());
+        let mut slots: Vec<Option<A>> = (0..len).map(|_| None).collect();
+        thread::scope(|s| {
+            let workers: Vec<_> = (0..max_threads.min(len))
+                .map(|_| s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        // Release the lock before running the effect
+                        let next = queue.lock().unwrap().next();
+                        match next {
+                            Some((i, e)) => done.push((i, e())),
+                            None => return done,
+                        }
+                    }
+                }))
+                .collect();
+            for worker in workers {
+                for (i, a) in worker.join().expect("effect panicked") {
+                    slots[i] = Some(a);
+                }
+            }
+        });
+        slots.into_iter().map(|a| a.expect("every effect is run")).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timeout_returns_fast_result() {
+        assert_eq!(timeout(|| 5, Duration::from_secs(5))(), Ok(5));
+    }
+
+    #[test]
+    fn timeout_gives_up_on_slow_effect() {
+        let slow = || thread::sleep(Duration::from_millis(200));
+        assert_eq!(timeout(slow, Duration::from_millis(10))(), Err(TimeoutError));
+    }
+
+    #[test]
+    fn until_deadline_returns_result_before_deadline() {
+        use EffectMonad;
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!((|| 5).until_deadline(deadline)(), Ok(5));
+    }
+
+    #[test]
+    fn until_deadline_gives_up_after_deadline() {
+        use EffectMonad;
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        let slow = || thread::sleep(Duration::from_millis(200));
+        assert_eq!(slow.until_deadline(deadline)(), Err(DeadlineExceeded));
+        assert_eq!((|| 5).until_deadline(deadline)(), Err(DeadlineExceeded));
+    }
+
+    #[test]
+    fn sequence_to_receiver_yields_results_in_order() {
+        let effects = (0..4).map(|i| move || {
+            thread::sleep(Duration::from_millis(5));
+            i
+        }).collect();
+        let rx = sequence_to_receiver(effects);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn race_ok_returns_slow_success_over_fast_failure() {
+        let result = race_ok(|| Err::<i32, _>("fast"), || {
+            thread::sleep(Duration::from_millis(20));
+            Ok(1)
+        })();
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn race_ok_collects_both_errors_in_order() {
+        let result = race_ok(|| {
+            thread::sleep(Duration::from_millis(20));
+            Err::<(), _>("a")
+        }, || Err("b"))();
+        assert_eq!(result, Err(("a", "b")));
+    }
+
+    #[test]
+    fn spawn_config_names_threads() {
+        use std::string::String;
+
+        let name = || thread::current().name().map(String::from);
+        let config = SpawnConfig::new().name("effect-worker").stack_size(64 * 1024);
+        let result = timeout_with(config.clone(), name, Duration::from_secs(5))();
+        assert_eq!(result, Ok(Some("effect-worker".to_string())));
+
+        let result = race_ok_with(config, move || name().ok_or(()), move || name().ok_or(()))();
+        assert_eq!(result, Ok("effect-worker".to_string()));
+    }
+
+    #[test]
+    fn spawn_joins_with_result() {
+        use EffectMonad;
+
+        assert_eq!((|| 6 * 7).spawn().join().ok(), Some(42));
+    }
+
+    #[test]
+    fn spawn_with_uses_config() {
+        use std::string::String;
+        use EffectMonad;
+
+        let config = SpawnConfig::new().name("spawned-effect");
+        let name = (|| thread::current().name().map(String::from)).spawn_with(&config).join().ok();
+        assert_eq!(name, Some(Some("spawned-effect".to_string())));
+    }
+
+    #[test]
+    fn spawn_join_surfaces_panic() {
+        use EffectMonad;
+
+        assert!((|| -> i32 { panic!("background") }).spawn().join().is_err());
+    }
+
+    #[test]
+    fn par_sequence_bounded_preserves_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let effects = [40, 10, 30, 20].iter().enumerate().map(|(i, &ms)| {
+            let (running, peak) = (&running, &peak);
+            move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(ms));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        }).collect();
+        assert_eq!(par_sequence_bounded(effects, 2)(), vec![0, 1, 2, 3]);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,158 @@
+//! Combinators which control when effects run.
+
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+use std::vec::Vec;
+
+/// Total time spent in each named stage, across every thread.
+static STAGE_TIMINGS: Mutex<BTreeMap<&'static str, Duration>> = Mutex::new(BTreeMap::new());
+
+/// Effect returned by `EffectMonad::stage`.
+pub struct Stage<Ea> {
+    pub(crate) e: Ea,
+    pub(crate) name: &'static str,
+}
+
+impl<A, Ea> FnOnce<()> for Stage<Ea>
+    where Ea: FnOnce() -> A,
+{
+    type Output = A;
+    extern "rust-call" fn call_once(self, _: ()) -> Self::Output {
+        let start = Instant::now();
+        let a = (self.e)();
+        let elapsed = start.elapsed();
+        let mut timings = STAGE_TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+        *timings.entry(self.name).or_default() += elapsed;
+        a
+    }
+}
+
+/// Returns the total time spent in each stage created by
+/// `EffectMonad::stage`, keyed by stage name.
+///
+/// Timings accumulate across every run of every stage with the same name, on
+/// every thread, until they're cleared with `reset_stage_timings`.
+pub fn stage_timings() -> BTreeMap<&'static str, Duration> {
+    STAGE_TIMINGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Clears the timings collected for every stage.
+pub fn reset_stage_timings() {
+    STAGE_TIMINGS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Wraps the repeatable effect `e` so that at least `min_interval` passes
+/// between the starts of successive runs.
+///
+/// A call made too soon sleeps until the interval has passed. The wrapper
+/// tracks when it last ran, so it is an `FnMut`.
+pub fn throttle<A, E>(e: E, min_interval: Duration) -> impl FnMut() -> A
+    where E: Fn() -> A,
+{
+    let mut last: Option<Instant> = None;
+    move || {
+        if let Some(last) = last {
+            let elapsed = last.elapsed();
+            if elapsed < min_interval {
+                thread::sleep(min_interval - elapsed);
+            }
+        }
+        last = Some(Instant::now());
+        e()
+    }
+}
+
+/// Wraps the repeatable effect `e` so that calls arriving within `quiet` of
+/// the last accepted call are dropped.
+///
+/// An accepted call runs `e` and returns `Some` with its result; a dropped
+/// call returns `None` without running anything. The first call is always
+/// accepted. The wrapper tracks when it last accepted a call, so it is an
+/// `FnMut`.
+pub fn debounce<A, E>(e: E, quiet: Duration) -> impl FnMut() -> Option<A>
+    where E: Fn() -> A,
+{
+    let mut last: Option<Instant> = None;
+    move || {
+        if let Some(last) = last {
+            if last.elapsed() < quiet {
+                return None;
+            }
+        }
+        last = Some(Instant::now());
+        Some(e())
+    }
+}
+
+/// Creates an effect which runs each of `effects` in order, collecting their
+/// results, until `budget` has been used up.
+///
+/// The elapsed time is checked before starting each effect, so an effect
+/// which starts within the budget always finishes, even if it overruns it.
+/// Effects after that are never run.
+pub fn sequence_within<A, E, I>(effects: I, budget: Duration) -> impl FnOnce() -> Vec<A>
+    where E: FnOnce() -> A,
+          I: IntoIterator<Item = E>,
+{
+    move || {
+        let start = Instant::now();
+        let mut results = Vec::new();
+        for e in effects {
+            if start.elapsed() >= budget {
+                break;
+            }
+            results.push(e());
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn throttle_spaces_out_calls() {
+        let interval = Duration::from_millis(20);
+        let mut throttled = throttle(Instant::now, interval);
+        let first = throttled();
+        let second = throttled();
+        assert!(second - first >= interval);
+    }
+
+    #[test]
+    fn debounce_drops_calls_within_quiet_period() {
+        let quiet = Duration::from_millis(50);
+        let mut debounced = debounce(|| 1, quiet);
+        assert_eq!(debounced(), Some(1));
+        assert_eq!(debounced(), None);
+        thread::sleep(quiet);
+        assert_eq!(debounced(), Some(1));
+    }
+
+    #[test]
+    fn sequence_within_stops_when_budget_is_spent() {
+        let effects = (0..5).map(|i| move || {
+            thread::sleep(Duration::from_millis(100));
+            i
+        });
+        assert_eq!(sequence_within(effects, Duration::from_millis(250))(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn stage_records_timings_by_name() {
+        use EffectMonad;
+
+        // The registry is global, so use names no other test records under
+        let chain = (|| thread::sleep(Duration::from_millis(20)))
+            .stage("stage_test_load")
+            .bind(|_| (|| thread::sleep(Duration::from_millis(10))).stage("stage_test_parse"));
+        chain();
+        let timings = stage_timings();
+        assert!(timings["stage_test_load"] >= Duration::from_millis(20));
+        assert!(timings["stage_test_parse"] >= Duration::from_millis(10));
+    }
+}